@@ -0,0 +1,295 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+
+declare_id!("78nvFuM9WdGX9wf3gHpmMNahz8xDWmKm9v3v2MrzCgHD");
+
+// Constants
+const POOL_SEED: &[u8] = b"pool";
+const POOL_VAULT_SEED: &[u8] = b"pool_vault";
+const BASIS_POINTS: u64 = 10000;
+
+// Minimal constant-product AMM for localnet tests. `route` has the same
+// discriminator, arguments and leading accounts as Jupiter v6 `route`, so the
+// vault can trade through it with the Jupiter swap venue.
+#[program]
+pub mod mock_dex {
+    use super::*;
+
+    // Create a pool for a token pair
+    pub fn initialize_pool(
+        ctx: Context<InitializePool>,
+        fee_bps: u16,
+    ) -> Result<()> {
+        require!(
+            (fee_bps as u64) < BASIS_POINTS,
+            MockDexError::InvalidFee
+        );
+
+        let pool = &mut ctx.accounts.pool;
+        pool.mint_a = ctx.accounts.mint_a.key();
+        pool.mint_b = ctx.accounts.mint_b.key();
+        pool.vault_a = ctx.accounts.vault_a.key();
+        pool.vault_b = ctx.accounts.vault_b.key();
+        pool.fee_bps = fee_bps;
+        pool.bump = ctx.bumps.pool;
+
+        msg!("Pool initialized: {} / {}", pool.mint_a, pool.mint_b);
+        Ok(())
+    }
+
+    // Deposit reserves into a pool, no LP tokens are issued
+    pub fn add_liquidity(
+        ctx: Context<AddLiquidity>,
+        amount_a: u64,
+        amount_b: u64,
+    ) -> Result<()> {
+        for (from, to, amount) in [
+            (&ctx.accounts.provider_token_a, &ctx.accounts.vault_a, amount_a),
+            (&ctx.accounts.provider_token_b, &ctx.accounts.vault_b, amount_b),
+        ] {
+            let cpi_accounts = Transfer {
+                from: from.to_account_info(),
+                to: to.to_account_info(),
+                authority: ctx.accounts.provider.to_account_info(),
+            };
+            let cpi_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                cpi_accounts,
+            );
+            token::transfer(cpi_ctx, amount)?;
+        }
+
+        Ok(())
+    }
+
+    // Jupiter-compatible exact-in swap through a single pool
+    pub fn route(
+        ctx: Context<Route>,
+        _route_plan: Vec<RoutePlanStep>,
+        in_amount: u64,
+        quoted_out_amount: u64,
+        slippage_bps: u16,
+        platform_fee_bps: u8,
+    ) -> Result<()> {
+        require!(platform_fee_bps == 0, MockDexError::PlatformFeeNotSupported);
+
+        let pool = &ctx.accounts.pool;
+        let reserve_in = ctx.accounts.pool_source_vault.amount;
+        let reserve_out = ctx.accounts.pool_destination_vault.amount;
+
+        // x * y = k with the fee taken from the input
+        let in_after_fee = in_amount as u128 * (BASIS_POINTS - pool.fee_bps as u64) as u128 / BASIS_POINTS as u128;
+        let out_amount = (reserve_out as u128 * in_after_fee / (reserve_in as u128 + in_after_fee)) as u64;
+
+        // Same slippage rule as Jupiter
+        let min_out = quoted_out_amount as u128 * (BASIS_POINTS as u128 - slippage_bps as u128) / BASIS_POINTS as u128;
+        require!(
+            out_amount as u128 >= min_out,
+            MockDexError::SlippageToleranceExceeded
+        );
+
+        // Pull input from the user
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_source_token_account.to_account_info(),
+            to: ctx.accounts.pool_source_vault.to_account_info(),
+            authority: ctx.accounts.user_transfer_authority.to_account_info(),
+        };
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+        );
+        token::transfer(cpi_ctx, in_amount)?;
+
+        // Pay output to the destination, or the user when none is given
+        let destination = match &ctx.accounts.destination_token_account {
+            Some(account) => account.to_account_info(),
+            None => ctx.accounts.user_destination_token_account.to_account_info(),
+        };
+
+        let pool_seeds = &[
+            POOL_SEED,
+            pool.mint_a.as_ref(),
+            pool.mint_b.as_ref(),
+            &[pool.bump],
+        ];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.pool_destination_vault.to_account_info(),
+            to: destination,
+            authority: ctx.accounts.pool.to_account_info(),
+        };
+        let cpi_signer_seeds = &[&pool_seeds[..]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            cpi_signer_seeds,
+        );
+        token::transfer(cpi_ctx, out_amount)?;
+
+        emit!(Swapped {
+            pool: pool.key(),
+            in_amount,
+            out_amount,
+        });
+
+        Ok(())
+    }
+}
+
+// Jupiter route plan step. The mock swaps through the pool accounts and
+// ignores the plan, it is only accepted for interface compatibility.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RoutePlanStep {
+    pub swap: u8,
+    pub percent: u8,
+    pub input_index: u8,
+    pub output_index: u8,
+}
+
+#[event]
+pub struct Swapped {
+    pub pool: Pubkey,
+    pub in_amount: u64,
+    pub out_amount: u64,
+}
+
+// Error code
+#[error_code]
+pub enum MockDexError {
+    #[msg("Invalid pool fee")]
+    InvalidFee,
+
+    #[msg("Platform fees are not supported")]
+    PlatformFeeNotSupported,
+
+    #[msg("Slippage tolerance exceeded")]
+    SlippageToleranceExceeded,
+
+    #[msg("Pool vault does not match the swap direction")]
+    InvalidPoolVault,
+}
+
+// Pool account structure
+#[account]
+pub struct Pool {
+    pub mint_a: Pubkey,   // Token A mint
+    pub mint_b: Pubkey,   // Token B mint
+    pub vault_a: Pubkey,  // Token A reserve
+    pub vault_b: Pubkey,  // Token B reserve
+    pub fee_bps: u16,     // Swap fee
+    pub bump: u8,         // PDA bump
+}
+
+// Initialize pool instruction
+#[derive(Accounts)]
+pub struct InitializePool<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + 32 + 32 + 32 + 32 + 2 + 1,
+        seeds = [POOL_SEED, mint_a.key().as_ref(), mint_b.key().as_ref()],
+        bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    pub mint_a: Account<'info, Mint>,
+    pub mint_b: Account<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        token::mint = mint_a,
+        token::authority = pool,
+        seeds = [POOL_VAULT_SEED, pool.key().as_ref(), mint_a.key().as_ref()],
+        bump
+    )]
+    pub vault_a: Account<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        token::mint = mint_b,
+        token::authority = pool,
+        seeds = [POOL_VAULT_SEED, pool.key().as_ref(), mint_b.key().as_ref()],
+        bump
+    )]
+    pub vault_b: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+// Add liquidity instruction
+#[derive(Accounts)]
+pub struct AddLiquidity<'info> {
+    pub provider: Signer<'info>,
+
+    #[account(
+        has_one = vault_a,
+        has_one = vault_b
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(mut)]
+    pub vault_a: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub vault_b: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub provider_token_a: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub provider_token_b: Account<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+// Route instruction, accounts up to `program` follow Jupiter v6 `route`
+#[derive(Accounts)]
+pub struct Route<'info> {
+    pub token_program: Program<'info, Token>,
+
+    pub user_transfer_authority: Signer<'info>,
+
+    #[account(mut)]
+    pub user_source_token_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub user_destination_token_account: Account<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub destination_token_account: Option<Account<'info, TokenAccount>>,
+
+    pub destination_mint: Account<'info, Mint>,
+
+    /// CHECK: Unused, platform fees are rejected
+    #[account(mut)]
+    pub platform_fee_account: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Unused, kept for Jupiter account compatibility
+    pub event_authority: UncheckedAccount<'info>,
+
+    /// CHECK: Unused, kept for Jupiter account compatibility
+    pub program: UncheckedAccount<'info>,
+
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        mut,
+        constraint = (pool_source_vault.key() == pool.vault_a && pool_destination_vault.key() == pool.vault_b)
+            || (pool_source_vault.key() == pool.vault_b && pool_destination_vault.key() == pool.vault_a)
+            @ MockDexError::InvalidPoolVault,
+        constraint = pool_source_vault.mint == user_source_token_account.mint @ MockDexError::InvalidPoolVault
+    )]
+    pub pool_source_vault: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = pool_destination_vault.mint == destination_mint.key() @ MockDexError::InvalidPoolVault
+    )]
+    pub pool_destination_vault: Account<'info, TokenAccount>,
+}
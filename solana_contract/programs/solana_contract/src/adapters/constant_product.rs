@@ -0,0 +1,47 @@
+use anchor_lang::prelude::*;
+
+use super::{check_amounts, key_at, ExpectedSwap};
+use crate::MyVaultError;
+
+// SPL Token Swap style `Swap` instruction: tag (u8), amount_in (u64),
+// minimum_amount_out (u64)
+pub(super) const SWAP_TAG: u8 = 1;
+const SWAP_DATA_SIZE: usize = 1 + 8 + 8;
+
+// Swap account positions
+const USER_TRANSFER_AUTHORITY: usize = 2;
+const SOURCE: usize = 3;
+const DESTINATION: usize = 6;
+
+// Check a constant-product pool swap between the vault's own token accounts
+pub fn validate_swap(
+    data: &[u8],
+    accounts: &[AccountInfo],
+    expected: &ExpectedSwap,
+) -> Result<()> {
+    require!(
+        data.len() == SWAP_DATA_SIZE && data[0] == SWAP_TAG,
+        MyVaultError::UnsupportedRouteInstruction
+    );
+
+    let amount_in = u64::from_le_bytes(data[1..9].try_into().unwrap());
+    let minimum_amount_out = u64::from_le_bytes(data[9..17].try_into().unwrap());
+
+    require_keys_eq!(
+        key_at(accounts, USER_TRANSFER_AUTHORITY)?,
+        expected.transfer_authority,
+        MyVaultError::RouteMismatch
+    );
+    require_keys_eq!(
+        key_at(accounts, SOURCE)?,
+        expected.source_token_account,
+        MyVaultError::RouteMismatch
+    );
+    require_keys_eq!(
+        key_at(accounts, DESTINATION)?,
+        expected.destination_token_account,
+        MyVaultError::RouteMismatch
+    );
+
+    check_amounts(amount_in, minimum_amount_out, true, expected)
+}
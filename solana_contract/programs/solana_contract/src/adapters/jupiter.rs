@@ -0,0 +1,159 @@
+use anchor_lang::prelude::*;
+
+use super::{check_amounts, key_at, ExpectedSwap};
+use crate::{MyVaultError, BASIS_POINTS};
+
+// Jupiter v6 instruction discriminators
+pub(super) const ROUTE_DISCRIMINATOR: [u8; 8] = [229, 23, 203, 151, 122, 227, 173, 42];
+pub(super) const SHARED_ACCOUNTS_ROUTE_DISCRIMINATOR: [u8; 8] = [193, 32, 155, 51, 65, 214, 156, 129];
+pub(super) const EXACT_OUT_ROUTE_DISCRIMINATOR: [u8; 8] = [208, 51, 239, 151, 123, 43, 237, 92];
+
+// All route instructions end with: amount (u64), quoted amount (u64),
+// slippage_bps (u16), platform_fee_bps (u8). The route plan before them is
+// variable length and not decoded here.
+const ROUTE_TAIL_SIZE: usize = 8 + 8 + 2 + 1;
+
+// Route fields the vault cares about, read from the instruction data and
+// the route accounts at their fixed positions
+pub struct JupiterRoute {
+    pub transfer_authority: Pubkey,
+    pub source_token_account: Pubkey,
+    pub destination_token_account: Pubkey,
+    // Optional final recipient, route and exact_out_route only
+    pub recipient_token_account: Option<Pubkey>,
+    // route has no source mint account
+    pub source_mint: Option<Pubkey>,
+    pub destination_mint: Pubkey,
+    // Exact input, or exact output for exact_out_route
+    pub amount: u64,
+    // Minimum out after slippage, or maximum in for exact_out_route
+    pub other_amount_threshold: u64,
+    pub amount_is_input: bool,
+    pub platform_fee_bps: u8,
+}
+
+// Decode a route / shared_accounts_route / exact_out_route instruction
+pub fn decode_route(
+    jupiter_program: &Pubkey,
+    data: &[u8],
+    accounts: &[AccountInfo],
+) -> Result<JupiterRoute> {
+    require!(
+        data.len() >= 8 + ROUTE_TAIL_SIZE,
+        MyVaultError::UnsupportedRouteInstruction
+    );
+
+    let tail = &data[data.len() - ROUTE_TAIL_SIZE..];
+    let amount = u64::from_le_bytes(tail[0..8].try_into().unwrap());
+    let quoted_amount = u64::from_le_bytes(tail[8..16].try_into().unwrap());
+    let slippage_bps = u16::from_le_bytes(tail[16..18].try_into().unwrap()) as u128;
+    let platform_fee_bps = tail[18];
+
+    let key_at = |index: usize| key_at(accounts, index);
+    // Anchor encodes a missing optional account as the program id
+    let optional_key_at = |index: usize| -> Result<Option<Pubkey>> {
+        let key = key_at(index)?;
+        Ok(if key == *jupiter_program { None } else { Some(key) })
+    };
+
+    // Minimum out / maximum in that Jupiter enforces after slippage
+    let bps = BASIS_POINTS as u128;
+    let min_out = |quoted_out: u64| (quoted_out as u128 * bps.saturating_sub(slippage_bps) / bps) as u64;
+    let max_in = |quoted_in: u64| {
+        let scaled = quoted_in as u128 * (bps + slippage_bps);
+        u64::try_from(scaled.div_ceil(bps)).unwrap_or(u64::MAX)
+    };
+
+    match data[..8].try_into().unwrap() {
+        ROUTE_DISCRIMINATOR => Ok(JupiterRoute {
+            transfer_authority: key_at(1)?,
+            source_token_account: key_at(2)?,
+            destination_token_account: key_at(3)?,
+            recipient_token_account: optional_key_at(4)?,
+            source_mint: None,
+            destination_mint: key_at(5)?,
+            amount,
+            other_amount_threshold: min_out(quoted_amount),
+            amount_is_input: true,
+            platform_fee_bps,
+        }),
+        SHARED_ACCOUNTS_ROUTE_DISCRIMINATOR => Ok(JupiterRoute {
+            transfer_authority: key_at(2)?,
+            source_token_account: key_at(3)?,
+            destination_token_account: key_at(6)?,
+            recipient_token_account: None,
+            source_mint: Some(key_at(7)?),
+            destination_mint: key_at(8)?,
+            amount,
+            other_amount_threshold: min_out(quoted_amount),
+            amount_is_input: true,
+            platform_fee_bps,
+        }),
+        EXACT_OUT_ROUTE_DISCRIMINATOR => Ok(JupiterRoute {
+            transfer_authority: key_at(1)?,
+            source_token_account: key_at(2)?,
+            destination_token_account: key_at(3)?,
+            recipient_token_account: optional_key_at(4)?,
+            source_mint: Some(key_at(5)?),
+            destination_mint: key_at(6)?,
+            // quoted amount is the input estimate
+            amount,
+            other_amount_threshold: max_in(quoted_amount),
+            amount_is_input: false,
+            platform_fee_bps,
+        }),
+        _ => err!(MyVaultError::UnsupportedRouteInstruction),
+    }
+}
+
+// Check that a route swaps the signal amount between the vault's own token
+// accounts with at least the signal minimum out
+pub fn validate_route(
+    jupiter_program: &Pubkey,
+    data: &[u8],
+    accounts: &[AccountInfo],
+    expected: &ExpectedSwap,
+) -> Result<()> {
+    let route = decode_route(jupiter_program, data, accounts)?;
+
+    require_keys_eq!(
+        route.transfer_authority,
+        expected.transfer_authority,
+        MyVaultError::RouteMismatch
+    );
+    require_keys_eq!(
+        route.source_token_account,
+        expected.source_token_account,
+        MyVaultError::RouteMismatch
+    );
+    require_keys_eq!(
+        route.destination_token_account,
+        expected.destination_token_account,
+        MyVaultError::RouteMismatch
+    );
+    if let Some(recipient) = route.recipient_token_account {
+        require_keys_eq!(
+            recipient,
+            expected.destination_token_account,
+            MyVaultError::RouteMismatch
+        );
+    }
+    if let Some(source_mint) = route.source_mint {
+        require_keys_eq!(source_mint, expected.source_mint, MyVaultError::RouteMismatch);
+    }
+    require_keys_eq!(
+        route.destination_mint,
+        expected.destination_mint,
+        MyVaultError::RouteMismatch
+    );
+
+    // Platform fees would be paid out of the vault's swap proceeds
+    require!(route.platform_fee_bps == 0, MyVaultError::RouteMismatch);
+
+    check_amounts(
+        route.amount,
+        route.other_amount_threshold,
+        route.amount_is_input,
+        expected,
+    )
+}
@@ -0,0 +1,475 @@
+use anchor_lang::prelude::*;
+
+use crate::{MyVaultError, SwapProgram, SwapVenue};
+
+pub mod constant_product;
+pub mod jupiter;
+pub mod orca_whirlpool;
+pub mod raydium_clmm;
+
+// What the signal being executed expects the swap to do
+pub struct ExpectedSwap {
+    pub transfer_authority: Pubkey,
+    pub source_token_account: Pubkey,
+    pub destination_token_account: Pubkey,
+    pub source_mint: Pubkey,
+    pub destination_mint: Pubkey,
+    pub amount_in: u64,
+    pub min_amount_out: u64,
+}
+
+// Check venue-native swap instruction data and accounts against the expected
+// swap. The swap program must be allowlisted for the pair's venue, so its
+// instructions are decoded with the right layout. Output is measured by the
+// caller from vault balances after the CPI, which works the same for every
+// venue and does not rely on decoding.
+pub fn validate_route(
+    venue: SwapVenue,
+    swap_program: &SwapProgram,
+    data: &[u8],
+    accounts: &[AccountInfo],
+    expected: &ExpectedSwap,
+) -> Result<()> {
+    require!(swap_program.venue == venue, MyVaultError::SwapVenueMismatch);
+
+    match venue {
+        SwapVenue::Jupiter => jupiter::validate_route(&swap_program.program_id, data, accounts, expected),
+        SwapVenue::OrcaWhirlpool => orca_whirlpool::validate_swap(data, accounts, expected),
+        SwapVenue::RaydiumClmm => raydium_clmm::validate_swap(data, accounts, expected),
+        SwapVenue::ConstantProduct => constant_product::validate_swap(data, accounts, expected),
+    }
+}
+
+// Check the amount and threshold of a swap that either spends an exact input
+// (threshold is the minimum out) or buys an exact output (threshold is the
+// maximum in)
+fn check_amounts(
+    amount: u64,
+    other_amount_threshold: u64,
+    amount_is_input: bool,
+    expected: &ExpectedSwap,
+) -> Result<()> {
+    let (max_in, min_out) = if amount_is_input {
+        require!(amount == expected.amount_in, MyVaultError::RouteMismatch);
+        (amount, other_amount_threshold)
+    } else {
+        (other_amount_threshold, amount)
+    };
+
+    require!(
+        max_in <= expected.amount_in && min_out >= expected.min_amount_out,
+        MyVaultError::RouteMismatch
+    );
+    Ok(())
+}
+
+fn key_at(accounts: &[AccountInfo], index: usize) -> Result<Pubkey> {
+    accounts
+        .get(index)
+        .map(|a| a.key())
+        .ok_or_else(|| error!(MyVaultError::RouteMismatch))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAM: Pubkey = Pubkey::new_from_array([8; 32]);
+    const VAULT_AUTHORITY: Pubkey = Pubkey::new_from_array([1; 32]);
+    const VAULT_SOURCE: Pubkey = Pubkey::new_from_array([2; 32]);
+    const VAULT_DESTINATION: Pubkey = Pubkey::new_from_array([3; 32]);
+    const SOURCE_MINT: Pubkey = Pubkey::new_from_array([4; 32]);
+    const DESTINATION_MINT: Pubkey = Pubkey::new_from_array([5; 32]);
+    const OTHER: Pubkey = Pubkey::new_from_array([9; 32]);
+
+    // Signal swapping 1_000 source units for at least 950 out
+    fn expected_swap() -> ExpectedSwap {
+        ExpectedSwap {
+            transfer_authority: VAULT_AUTHORITY,
+            source_token_account: VAULT_SOURCE,
+            destination_token_account: VAULT_DESTINATION,
+            source_mint: SOURCE_MINT,
+            destination_mint: DESTINATION_MINT,
+            amount_in: 1_000,
+            min_amount_out: 950,
+        }
+    }
+
+    // Run f over empty accounts with the given keys
+    fn with_accounts<R>(keys: &[Pubkey], f: impl FnOnce(&[AccountInfo]) -> R) -> R {
+        let owner = Pubkey::default();
+        let mut lamports = vec![0u64; keys.len()];
+        let mut data = vec![Vec::<u8>::new(); keys.len()];
+        let accounts: Vec<AccountInfo> = keys
+            .iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .map(|((key, lamports), data)| AccountInfo::new(key, false, false, lamports, data, &owner, false, 0))
+            .collect();
+        f(&accounts)
+    }
+
+    // Route data with an opaque route plan between the discriminator and the
+    // amount, quoted amount, slippage_bps and platform_fee_bps tail
+    fn jupiter_data(discriminator: [u8; 8], amount: u64, quoted_amount: u64, slippage_bps: u16, platform_fee_bps: u8) -> Vec<u8> {
+        let mut data = discriminator.to_vec();
+        data.extend_from_slice(&[0xaa; 12]);
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.extend_from_slice(&quoted_amount.to_le_bytes());
+        data.extend_from_slice(&slippage_bps.to_le_bytes());
+        data.push(platform_fee_bps);
+        data
+    }
+
+    // token_program, user_transfer_authority, user_source_token_account,
+    // user_destination_token_account, destination_token_account (optional),
+    // destination_mint, ... For exact_out_route the source mint comes before
+    // the destination mint.
+    fn jupiter_route_accounts(recipient: Pubkey, mints: &[Pubkey]) -> Vec<Pubkey> {
+        let mut keys = vec![OTHER, VAULT_AUTHORITY, VAULT_SOURCE, VAULT_DESTINATION, recipient];
+        keys.extend_from_slice(mints);
+        keys.push(OTHER);
+        keys
+    }
+
+    fn whirlpool_data(amount: u64, other_amount_threshold: u64, amount_specified_is_input: bool, a_to_b: bool) -> Vec<u8> {
+        let mut data = orca_whirlpool::SWAP_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.extend_from_slice(&other_amount_threshold.to_le_bytes());
+        data.extend_from_slice(&0u128.to_le_bytes());
+        data.push(amount_specified_is_input as u8);
+        data.push(a_to_b as u8);
+        data
+    }
+
+    // token_program, token_authority, whirlpool, token_owner_account_a,
+    // token_vault_a, token_owner_account_b, token_vault_b, tick arrays, oracle
+    fn whirlpool_accounts(a_to_b: bool) -> Vec<Pubkey> {
+        let (a, b) = if a_to_b {
+            (VAULT_SOURCE, VAULT_DESTINATION)
+        } else {
+            (VAULT_DESTINATION, VAULT_SOURCE)
+        };
+        vec![OTHER, VAULT_AUTHORITY, OTHER, a, OTHER, b, OTHER, OTHER, OTHER, OTHER, OTHER]
+    }
+
+    fn raydium_data(discriminator: [u8; 8], amount: u64, other_amount_threshold: u64, is_base_input: bool) -> Vec<u8> {
+        let mut data = discriminator.to_vec();
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.extend_from_slice(&other_amount_threshold.to_le_bytes());
+        data.extend_from_slice(&0u128.to_le_bytes());
+        data.push(is_base_input as u8);
+        data
+    }
+
+    // payer, amm_config, pool_state, input_token_account, output_token_account,
+    // input_vault, output_vault, observation_state, token_program, then for
+    // swap_v2 token_program_2022, memo_program, input_vault_mint, output_vault_mint
+    fn raydium_accounts() -> Vec<Pubkey> {
+        let mut keys = vec![OTHER; 13];
+        keys[0] = VAULT_AUTHORITY;
+        keys[3] = VAULT_SOURCE;
+        keys[4] = VAULT_DESTINATION;
+        keys[11] = SOURCE_MINT;
+        keys[12] = DESTINATION_MINT;
+        keys
+    }
+
+    fn constant_product_data(amount_in: u64, minimum_amount_out: u64) -> Vec<u8> {
+        let mut data = vec![constant_product::SWAP_TAG];
+        data.extend_from_slice(&amount_in.to_le_bytes());
+        data.extend_from_slice(&minimum_amount_out.to_le_bytes());
+        data
+    }
+
+    // A swap instruction each venue accepts for the expected swap, and how
+    // it has to be rejected once changed
+    struct VenueCase {
+        name: &'static str,
+        venue: SwapVenue,
+        data: Vec<u8>,
+        keys: Vec<Pubkey>,
+        // Positions of the vault accounts and mints the venue checks
+        checked_accounts: Vec<usize>,
+        // Data that does not decode as a supported swap
+        unsupported_data: Vec<Vec<u8>>,
+        // Swaps of the wrong amount, below the minimum out or paying fees
+        mismatched_data: Vec<Vec<u8>>,
+    }
+
+    fn venue_cases() -> Vec<VenueCase> {
+        use jupiter::{EXACT_OUT_ROUTE_DISCRIMINATOR, ROUTE_DISCRIMINATOR, SHARED_ACCOUNTS_ROUTE_DISCRIMINATOR};
+        use raydium_clmm::{SWAP_DISCRIMINATOR as RAYDIUM_SWAP, SWAP_V2_DISCRIMINATOR as RAYDIUM_SWAP_V2};
+
+        let whirlpool_unknown = {
+            let mut data = whirlpool_data(1_000, 950, true, true);
+            data[0] ^= 1;
+            data
+        };
+
+        vec![
+            VenueCase {
+                name: "jupiter route",
+                venue: SwapVenue::Jupiter,
+                // 0.5% slippage on a 1_000 quote is at least 995 out
+                data: jupiter_data(ROUTE_DISCRIMINATOR, 1_000, 1_000, 50, 0),
+                // The program id in the recipient slot means no recipient
+                keys: jupiter_route_accounts(PROGRAM, &[DESTINATION_MINT]),
+                checked_accounts: vec![1, 2, 3, 4, 5],
+                unsupported_data: vec![
+                    jupiter_data([0; 8], 1_000, 1_000, 50, 0),
+                    // Too short to hold a discriminator and the tail
+                    jupiter_data(ROUTE_DISCRIMINATOR, 1_000, 1_000, 50, 0)[..26].to_vec(),
+                ],
+                mismatched_data: vec![
+                    jupiter_data(ROUTE_DISCRIMINATOR, 999, 1_000, 50, 0),
+                    // 6% slippage allows as little as 940 out
+                    jupiter_data(ROUTE_DISCRIMINATOR, 1_000, 1_000, 600, 0),
+                    jupiter_data(ROUTE_DISCRIMINATOR, 1_000, 1_000, 50, 1),
+                ],
+            },
+            VenueCase {
+                name: "jupiter route to a recipient",
+                venue: SwapVenue::Jupiter,
+                data: jupiter_data(ROUTE_DISCRIMINATOR, 1_000, 1_000, 50, 0),
+                keys: jupiter_route_accounts(VAULT_DESTINATION, &[DESTINATION_MINT]),
+                checked_accounts: vec![1, 2, 3, 4, 5],
+                unsupported_data: vec![],
+                mismatched_data: vec![],
+            },
+            VenueCase {
+                name: "jupiter shared_accounts_route",
+                venue: SwapVenue::Jupiter,
+                data: jupiter_data(SHARED_ACCOUNTS_ROUTE_DISCRIMINATOR, 1_000, 1_000, 50, 0),
+                // token_program, program_authority, user_transfer_authority,
+                // source_token_account, program_source_token_account,
+                // program_destination_token_account, destination_token_account,
+                // source_mint, destination_mint, ...
+                keys: vec![
+                    OTHER,
+                    OTHER,
+                    VAULT_AUTHORITY,
+                    VAULT_SOURCE,
+                    OTHER,
+                    OTHER,
+                    VAULT_DESTINATION,
+                    SOURCE_MINT,
+                    DESTINATION_MINT,
+                    OTHER,
+                ],
+                checked_accounts: vec![2, 3, 6, 7, 8],
+                unsupported_data: vec![],
+                mismatched_data: vec![jupiter_data(SHARED_ACCOUNTS_ROUTE_DISCRIMINATOR, 1_000, 1_000, 50, 1)],
+            },
+            VenueCase {
+                name: "jupiter exact_out_route",
+                venue: SwapVenue::Jupiter,
+                // Exactly 960 out for a 990 quote, at most 995 in after slippage
+                data: jupiter_data(EXACT_OUT_ROUTE_DISCRIMINATOR, 960, 990, 50, 0),
+                keys: jupiter_route_accounts(PROGRAM, &[SOURCE_MINT, DESTINATION_MINT]),
+                checked_accounts: vec![1, 2, 3, 4, 5, 6],
+                unsupported_data: vec![],
+                mismatched_data: vec![
+                    // May spend up to 1_005, more than the signal amount
+                    jupiter_data(EXACT_OUT_ROUTE_DISCRIMINATOR, 960, 1_000, 50, 0),
+                    jupiter_data(EXACT_OUT_ROUTE_DISCRIMINATOR, 949, 990, 50, 0),
+                ],
+            },
+            VenueCase {
+                name: "whirlpool a to b",
+                venue: SwapVenue::OrcaWhirlpool,
+                data: whirlpool_data(1_000, 950, true, true),
+                keys: whirlpool_accounts(true),
+                checked_accounts: vec![1, 3, 5],
+                unsupported_data: vec![whirlpool_unknown, whirlpool_data(1_000, 950, true, true)[..41].to_vec()],
+                mismatched_data: vec![
+                    whirlpool_data(1_000, 949, true, true),
+                    // Direction flag disagrees with the token accounts
+                    whirlpool_data(1_000, 950, true, false),
+                ],
+            },
+            VenueCase {
+                name: "whirlpool b to a exact output",
+                venue: SwapVenue::OrcaWhirlpool,
+                // Exactly 960 out for at most 1_000 in
+                data: whirlpool_data(960, 1_000, false, false),
+                keys: whirlpool_accounts(false),
+                checked_accounts: vec![1, 3, 5],
+                unsupported_data: vec![],
+                mismatched_data: vec![whirlpool_data(960, 1_001, false, false)],
+            },
+            VenueCase {
+                name: "raydium swap",
+                venue: SwapVenue::RaydiumClmm,
+                data: raydium_data(RAYDIUM_SWAP, 1_000, 950, true),
+                keys: raydium_accounts()[..9].to_vec(),
+                checked_accounts: vec![0, 3, 4],
+                unsupported_data: vec![
+                    raydium_data([0; 8], 1_000, 950, true),
+                    raydium_data(RAYDIUM_SWAP, 1_000, 950, true)[..40].to_vec(),
+                ],
+                mismatched_data: vec![
+                    raydium_data(RAYDIUM_SWAP, 999, 950, true),
+                    raydium_data(RAYDIUM_SWAP, 1_000, 949, true),
+                    // swap_v2 without its mint accounts
+                    raydium_data(RAYDIUM_SWAP_V2, 1_000, 950, true),
+                ],
+            },
+            VenueCase {
+                name: "raydium swap_v2 exact output",
+                venue: SwapVenue::RaydiumClmm,
+                data: raydium_data(RAYDIUM_SWAP_V2, 960, 1_000, false),
+                keys: raydium_accounts(),
+                checked_accounts: vec![0, 3, 4, 11, 12],
+                unsupported_data: vec![],
+                mismatched_data: vec![raydium_data(RAYDIUM_SWAP_V2, 960, 1_001, false)],
+            },
+            VenueCase {
+                name: "constant product",
+                venue: SwapVenue::ConstantProduct,
+                data: constant_product_data(1_000, 950),
+                // pool, pool_authority, user_transfer_authority, source,
+                // pool_source, pool_destination, destination
+                keys: vec![OTHER, OTHER, VAULT_AUTHORITY, VAULT_SOURCE, OTHER, OTHER, VAULT_DESTINATION],
+                checked_accounts: vec![2, 3, 6],
+                unsupported_data: vec![
+                    {
+                        let mut data = constant_product_data(1_000, 950);
+                        data[0] = 0;
+                        data
+                    },
+                    constant_product_data(1_000, 950)[..16].to_vec(),
+                ],
+                mismatched_data: vec![constant_product_data(1_001, 950), constant_product_data(1_000, 949)],
+            },
+        ]
+    }
+
+    #[test]
+    fn validates_each_venue_swap() {
+        for case in venue_cases() {
+            let swap_program = SwapProgram {
+                program_id: PROGRAM,
+                venue: case.venue,
+            };
+            let validate = |data: &[u8], keys: &[Pubkey]| {
+                with_accounts(keys, |accounts| {
+                    validate_route(case.venue, &swap_program, data, accounts, &expected_swap())
+                })
+            };
+
+            assert!(validate(&case.data, &case.keys).is_ok(), "{}", case.name);
+
+            for &index in &case.checked_accounts {
+                let mut keys = case.keys.clone();
+                keys[index] = OTHER;
+                assert_eq!(
+                    validate(&case.data, &keys).unwrap_err(),
+                    error!(MyVaultError::RouteMismatch),
+                    "{}: account {}",
+                    case.name,
+                    index
+                );
+            }
+
+            // Missing the last account the venue checks
+            let last = *case.checked_accounts.iter().max().unwrap();
+            assert_eq!(
+                validate(&case.data, &case.keys[..last]).unwrap_err(),
+                error!(MyVaultError::RouteMismatch),
+                "{}: missing accounts",
+                case.name
+            );
+
+            for data in &case.unsupported_data {
+                assert_eq!(
+                    validate(data, &case.keys).unwrap_err(),
+                    error!(MyVaultError::UnsupportedRouteInstruction),
+                    "{}: unsupported data",
+                    case.name
+                );
+            }
+            for data in &case.mismatched_data {
+                assert_eq!(
+                    validate(data, &case.keys).unwrap_err(),
+                    error!(MyVaultError::RouteMismatch),
+                    "{}: mismatched data",
+                    case.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_jupiter_slippage_bounds() {
+        let keys = jupiter_route_accounts(PROGRAM, &[DESTINATION_MINT]);
+        with_accounts(&keys, |accounts| {
+            let data = jupiter_data(jupiter::ROUTE_DISCRIMINATOR, 1_000, 1_000, 50, 0);
+            let route = jupiter::decode_route(&PROGRAM, &data, accounts).unwrap();
+            assert_eq!(route.recipient_token_account, None);
+            assert_eq!(route.other_amount_threshold, 995);
+            assert!(route.amount_is_input);
+        });
+
+        let keys = jupiter_route_accounts(PROGRAM, &[SOURCE_MINT, DESTINATION_MINT]);
+        with_accounts(&keys, |accounts| {
+            let data = jupiter_data(jupiter::EXACT_OUT_ROUTE_DISCRIMINATOR, 960, 990, 50, 0);
+            let route = jupiter::decode_route(&PROGRAM, &data, accounts).unwrap();
+            // 990 * 1.005 rounded up
+            assert_eq!(route.other_amount_threshold, 995);
+            assert!(!route.amount_is_input);
+        });
+    }
+
+    #[test]
+    fn requires_program_allowlisted_for_the_venue() {
+        let swap_program = SwapProgram {
+            program_id: PROGRAM,
+            venue: SwapVenue::OrcaWhirlpool,
+        };
+        // Constant-product swap data sent to a program allowlisted as Whirlpool
+        with_accounts(&[], |accounts| {
+            assert_eq!(
+                validate_route(
+                    SwapVenue::ConstantProduct,
+                    &swap_program,
+                    &constant_product_data(1_000, 950),
+                    accounts,
+                    &expected_swap()
+                )
+                .unwrap_err(),
+                error!(MyVaultError::SwapVenueMismatch)
+            );
+        });
+    }
+
+    #[test]
+    fn checks_exact_input_amounts() {
+        let expected = expected_swap();
+        assert!(check_amounts(1_000, 950, true, &expected).is_ok());
+        assert_eq!(
+            check_amounts(999, 950, true, &expected).unwrap_err(),
+            error!(MyVaultError::RouteMismatch)
+        );
+        assert_eq!(
+            check_amounts(1_000, 949, true, &expected).unwrap_err(),
+            error!(MyVaultError::RouteMismatch)
+        );
+    }
+
+    #[test]
+    fn checks_exact_output_amounts() {
+        let expected = expected_swap();
+        // Buying 960 out for at most 990 in
+        assert!(check_amounts(960, 990, false, &expected).is_ok());
+        assert_eq!(
+            check_amounts(960, 1_001, false, &expected).unwrap_err(),
+            error!(MyVaultError::RouteMismatch)
+        );
+        assert_eq!(
+            check_amounts(949, 990, false, &expected).unwrap_err(),
+            error!(MyVaultError::RouteMismatch)
+        );
+    }
+}
@@ -0,0 +1,58 @@
+use anchor_lang::prelude::*;
+
+use super::{check_amounts, key_at, ExpectedSwap};
+use crate::MyVaultError;
+
+// Whirlpool `swap` instruction discriminator
+pub(super) const SWAP_DISCRIMINATOR: [u8; 8] = [248, 198, 158, 145, 225, 117, 135, 200];
+
+// amount (u64), other_amount_threshold (u64), sqrt_price_limit (u128),
+// amount_specified_is_input (bool), a_to_b (bool)
+const SWAP_DATA_SIZE: usize = 8 + 8 + 8 + 16 + 1 + 1;
+
+// Swap account positions
+const TOKEN_AUTHORITY: usize = 1;
+const TOKEN_OWNER_ACCOUNT_A: usize = 3;
+const TOKEN_OWNER_ACCOUNT_B: usize = 5;
+
+// Check a direct Whirlpool swap between the vault's own token accounts. The
+// pool's mints are implied by the vault token accounts it pays from and to.
+pub fn validate_swap(
+    data: &[u8],
+    accounts: &[AccountInfo],
+    expected: &ExpectedSwap,
+) -> Result<()> {
+    require!(
+        data.len() == SWAP_DATA_SIZE && data[..8] == SWAP_DISCRIMINATOR,
+        MyVaultError::UnsupportedRouteInstruction
+    );
+
+    let amount = u64::from_le_bytes(data[8..16].try_into().unwrap());
+    let other_amount_threshold = u64::from_le_bytes(data[16..24].try_into().unwrap());
+    let amount_specified_is_input = data[40] != 0;
+    let a_to_b = data[41] != 0;
+
+    let (source, destination) = if a_to_b {
+        (TOKEN_OWNER_ACCOUNT_A, TOKEN_OWNER_ACCOUNT_B)
+    } else {
+        (TOKEN_OWNER_ACCOUNT_B, TOKEN_OWNER_ACCOUNT_A)
+    };
+
+    require_keys_eq!(
+        key_at(accounts, TOKEN_AUTHORITY)?,
+        expected.transfer_authority,
+        MyVaultError::RouteMismatch
+    );
+    require_keys_eq!(
+        key_at(accounts, source)?,
+        expected.source_token_account,
+        MyVaultError::RouteMismatch
+    );
+    require_keys_eq!(
+        key_at(accounts, destination)?,
+        expected.destination_token_account,
+        MyVaultError::RouteMismatch
+    );
+
+    check_amounts(amount, other_amount_threshold, amount_specified_is_input, expected)
+}
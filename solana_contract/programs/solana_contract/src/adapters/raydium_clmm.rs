@@ -0,0 +1,69 @@
+use anchor_lang::prelude::*;
+
+use super::{check_amounts, key_at, ExpectedSwap};
+use crate::MyVaultError;
+
+// Raydium CLMM `swap` / `swap_v2` instruction discriminators
+pub(super) const SWAP_DISCRIMINATOR: [u8; 8] = [248, 198, 158, 145, 225, 117, 135, 200];
+pub(super) const SWAP_V2_DISCRIMINATOR: [u8; 8] = [43, 4, 237, 11, 26, 201, 30, 98];
+
+// amount (u64), other_amount_threshold (u64), sqrt_price_limit_x64 (u128),
+// is_base_input (bool)
+const SWAP_DATA_SIZE: usize = 8 + 8 + 8 + 16 + 1;
+
+// Account positions shared by swap and swap_v2
+const PAYER: usize = 0;
+const INPUT_TOKEN_ACCOUNT: usize = 3;
+const OUTPUT_TOKEN_ACCOUNT: usize = 4;
+
+// swap_v2 only
+const INPUT_VAULT_MINT: usize = 11;
+const OUTPUT_VAULT_MINT: usize = 12;
+
+// Check a direct Raydium CLMM swap between the vault's own token accounts
+pub fn validate_swap(
+    data: &[u8],
+    accounts: &[AccountInfo],
+    expected: &ExpectedSwap,
+) -> Result<()> {
+    require!(
+        data.len() == SWAP_DATA_SIZE
+            && (data[..8] == SWAP_DISCRIMINATOR || data[..8] == SWAP_V2_DISCRIMINATOR),
+        MyVaultError::UnsupportedRouteInstruction
+    );
+
+    let amount = u64::from_le_bytes(data[8..16].try_into().unwrap());
+    let other_amount_threshold = u64::from_le_bytes(data[16..24].try_into().unwrap());
+    let is_base_input = data[40] != 0;
+
+    require_keys_eq!(
+        key_at(accounts, PAYER)?,
+        expected.transfer_authority,
+        MyVaultError::RouteMismatch
+    );
+    require_keys_eq!(
+        key_at(accounts, INPUT_TOKEN_ACCOUNT)?,
+        expected.source_token_account,
+        MyVaultError::RouteMismatch
+    );
+    require_keys_eq!(
+        key_at(accounts, OUTPUT_TOKEN_ACCOUNT)?,
+        expected.destination_token_account,
+        MyVaultError::RouteMismatch
+    );
+
+    if data[..8] == SWAP_V2_DISCRIMINATOR {
+        require_keys_eq!(
+            key_at(accounts, INPUT_VAULT_MINT)?,
+            expected.source_mint,
+            MyVaultError::RouteMismatch
+        );
+        require_keys_eq!(
+            key_at(accounts, OUTPUT_VAULT_MINT)?,
+            expected.destination_mint,
+            MyVaultError::RouteMismatch
+        );
+    }
+
+    check_amounts(amount, other_amount_threshold, is_base_input, expected)
+}
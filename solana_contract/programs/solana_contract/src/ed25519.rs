@@ -0,0 +1,71 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
+
+use crate::MyVaultError;
+
+// Ed25519 program instruction layout: [num_signatures: u8, padding: u8, offsets...]
+const OFFSETS_START: usize = 2;
+const OFFSETS_SIZE: usize = 14;
+const PUBKEY_SIZE: usize = 32;
+
+// Offsets pointing at data inside the Ed25519 instruction itself use this index
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+// Check that the instruction right before the current one is an Ed25519 program
+// instruction verifying `signer`'s signature over `message`. The signature itself
+// is checked by the Ed25519 program, the transaction fails if it is invalid.
+pub fn verify_preceding_signature(
+    instructions: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+    let current_index = load_current_index_checked(instructions)?;
+    require!(current_index > 0, MyVaultError::MissingSignatureInstruction);
+
+    let ix = load_instruction_at_checked((current_index - 1) as usize, instructions)?;
+    require_keys_eq!(
+        ix.program_id,
+        ed25519_program::ID,
+        MyVaultError::MissingSignatureInstruction
+    );
+
+    // Exactly one signature per instruction
+    let data = &ix.data;
+    require!(
+        data.len() >= OFFSETS_START + OFFSETS_SIZE && data[0] == 1,
+        MyVaultError::InvalidSignalSignature
+    );
+
+    let read_u16 = |at: usize| u16::from_le_bytes([data[OFFSETS_START + at], data[OFFSETS_START + at + 1]]);
+    let signature_instruction_index = read_u16(2);
+    let public_key_offset = read_u16(4) as usize;
+    let public_key_instruction_index = read_u16(6);
+    let message_data_offset = read_u16(8) as usize;
+    let message_data_size = read_u16(10) as usize;
+    let message_instruction_index = read_u16(12);
+
+    // Signature, key and message must all live in the Ed25519 instruction
+    require!(
+        signature_instruction_index == CURRENT_INSTRUCTION
+            && public_key_instruction_index == CURRENT_INSTRUCTION
+            && message_instruction_index == CURRENT_INSTRUCTION,
+        MyVaultError::InvalidSignalSignature
+    );
+
+    let public_key = data
+        .get(public_key_offset..public_key_offset + PUBKEY_SIZE)
+        .ok_or(MyVaultError::InvalidSignalSignature)?;
+    let signed_message = data
+        .get(message_data_offset..message_data_offset + message_data_size)
+        .ok_or(MyVaultError::InvalidSignalSignature)?;
+
+    require!(
+        public_key == signer.as_ref() && signed_message == message,
+        MyVaultError::InvalidSignalSignature
+    );
+
+    Ok(())
+}
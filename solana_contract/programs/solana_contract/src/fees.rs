@@ -0,0 +1,212 @@
+use anchor_lang::prelude::*;
+
+use crate::math::{self, Rounding};
+use crate::nav::{VIRTUAL_ASSETS, VIRTUAL_SHARES};
+use crate::{MyVaultError, Vault, BASIS_POINTS, PRICE_SCALE};
+
+pub const SECONDS_PER_YEAR: u64 = 365 * 24 * 60 * 60;
+
+// Fees crystallized by an accrual, paid by minting fee shares
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Accrual {
+    pub management_fee: u64,    // In base token units
+    pub performance_fee: u64,   // In base token units
+    pub shares: u64,            // Fee shares to mint
+    pub high_water_mark: u128,  // High-water mark after the accrual
+}
+
+impl Accrual {
+    // Whether the accrual charged the management fee up to now. A fee that
+    // rounds down to no shares keeps accruing from the last accrual, so
+    // frequent accruals cannot round it away.
+    pub fn settles_management_fee(&self, total_shares: u64, management_fee_bps: u16) -> bool {
+        self.shares > 0 || total_shares == 0 || management_fee_bps == 0
+    }
+}
+
+// Share price in base token units per share, scaled by PRICE_SCALE
+pub fn share_price(total_assets: u64, total_shares: u64) -> Result<u128> {
+    math::mul_div(
+        total_assets as u128 + VIRTUAL_ASSETS as u128,
+        PRICE_SCALE,
+        total_shares as u128 + VIRTUAL_SHARES as u128,
+        Rounding::Down,
+    )
+}
+
+// Fees accrued over `elapsed` seconds at the current NAV. The management fee
+// is charged on NAV pro rata over the year. The performance fee is charged on
+// the share price gain above the high-water mark, net of the management fee.
+// Fee shares are minted so that they are worth the fees after minting, and
+// the high-water mark moves up to the share price after the accrual.
+pub fn accrue(
+    total_assets: u64,
+    total_shares: u64,
+    management_fee_bps: u16,
+    performance_fee_bps: u16,
+    high_water_mark: u128,
+    elapsed: u64,
+) -> Result<Accrual> {
+    if total_shares == 0 {
+        return Ok(Accrual {
+            management_fee: 0,
+            performance_fee: 0,
+            shares: 0,
+            high_water_mark,
+        });
+    }
+
+    let management_fee = math::to_u64(math::mul_div(
+        (total_assets as u128)
+            .checked_mul(management_fee_bps as u128)
+            .ok_or(MyVaultError::MathOverflow)?,
+        elapsed as u128,
+        BASIS_POINTS as u128 * SECONDS_PER_YEAR as u128,
+        Rounding::Down,
+    )?)?
+    .min(total_assets);
+
+    let price = share_price(
+        total_assets.checked_sub(management_fee).ok_or(MyVaultError::MathOverflow)?,
+        total_shares,
+    )?;
+    let performance_fee = if price > high_water_mark {
+        let gain = math::to_u64(math::mul_div(
+            price - high_water_mark,
+            total_shares as u128 + VIRTUAL_SHARES as u128,
+            PRICE_SCALE,
+            Rounding::Down,
+        )?)?;
+        math::bps(gain, performance_fee_bps as u64, Rounding::Down)?
+    } else {
+        0
+    };
+
+    // fee * shares / (assets - fee), rounded down in favor of the investors
+    let fee = management_fee
+        .checked_add(performance_fee)
+        .ok_or(MyVaultError::MathOverflow)?
+        .min(total_assets);
+    let shares = math::mul_div_u64(
+        fee,
+        total_shares.checked_add(VIRTUAL_SHARES).ok_or(MyVaultError::MathOverflow)?,
+        total_assets
+            .checked_sub(fee)
+            .and_then(|assets| assets.checked_add(VIRTUAL_ASSETS))
+            .ok_or(MyVaultError::MathOverflow)?,
+        Rounding::Down,
+    )?;
+
+    let price = share_price(
+        total_assets,
+        total_shares.checked_add(shares).ok_or(MyVaultError::MathOverflow)?,
+    )?;
+    Ok(Accrual {
+        management_fee,
+        performance_fee,
+        shares,
+        high_water_mark: high_water_mark.max(price),
+    })
+}
+
+// Share supply including fees accrued since the vault's last accrual, so
+// views match the share math of the next deposit or withdraw
+pub fn total_shares_after_accrual(vault: &Vault, total_assets: u64, total_shares: u64, now: u64) -> Result<u64> {
+    let accrual = accrue(
+        total_assets,
+        total_shares,
+        vault.management_fee_bps,
+        vault.performance_fee_bps,
+        vault.high_water_mark,
+        now.saturating_sub(vault.last_fee_accrual),
+    )?;
+    total_shares
+        .checked_add(accrual.shares)
+        .ok_or_else(|| error!(MyVaultError::MathOverflow))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn charges_management_fee_over_time() {
+        // 2% a year on 1,000,000 units at a flat share price, half a year
+        let accrual = accrue(1_000_000, 1_000_000, 200, 0, PRICE_SCALE, SECONDS_PER_YEAR / 2).unwrap();
+        assert_eq!(accrual.management_fee, 10_000);
+        assert_eq!(accrual.performance_fee, 0);
+
+        // Fee shares are worth the fee after minting
+        let value = math::mul_div_u64(accrual.shares, 1_000_001, 1_000_001 + accrual.shares, Rounding::Down).unwrap();
+        assert!((9_999..=10_000).contains(&value));
+    }
+
+    #[test]
+    fn charges_performance_fee_above_high_water_mark() {
+        // Share price doubled, 20% of the gain
+        let accrual = accrue(2_000_000, 1_000_000, 0, 2_000, PRICE_SCALE, 0).unwrap();
+        assert_eq!(accrual.management_fee, 0);
+        // 20% of a 1,000,000 gain, rounded down in favor of the investors
+        assert_eq!(accrual.performance_fee, 199_999);
+        assert!(accrual.high_water_mark > PRICE_SCALE);
+        assert!(accrual.high_water_mark < 2 * PRICE_SCALE);
+
+        // No fee again until the price exceeds the new mark
+        let shares = 1_000_000 + accrual.shares;
+        let again = accrue(2_000_000, shares, 0, 2_000, accrual.high_water_mark, 0).unwrap();
+        assert_eq!(again.performance_fee, 0);
+        assert_eq!(again.shares, 0);
+    }
+
+    #[test]
+    fn no_performance_fee_below_high_water_mark() {
+        let accrual = accrue(900_000, 1_000_000, 0, 2_000, PRICE_SCALE, 0).unwrap();
+        assert_eq!(accrual.shares, 0);
+        assert_eq!(accrual.high_water_mark, PRICE_SCALE);
+    }
+
+    #[test]
+    fn frequent_accruals_keep_the_management_fee() {
+        // 2% a year on 1,000,000 units is under one unit per second
+        let accrual = accrue(1_000_000, 1_000_000, 200, 0, PRICE_SCALE, 1).unwrap();
+        assert_eq!(accrual.shares, 0);
+        assert!(!accrual.settles_management_fee(1_000_000, 200));
+
+        // The fee keeps accruing until it mints shares
+        let accrual = accrue(1_000_000, 1_000_000, 200, 0, PRICE_SCALE, 60 * 60).unwrap();
+        assert!(accrual.shares > 0);
+        assert!(accrual.settles_management_fee(1_000_000, 200));
+    }
+
+    #[test]
+    fn settles_without_shares_or_management_fee() {
+        let accrual = accrue(1_000_000, 0, 200, 0, PRICE_SCALE, 1).unwrap();
+        assert!(accrual.settles_management_fee(0, 200));
+        let accrual = accrue(1_000_000, 1_000_000, 0, 2_000, PRICE_SCALE, 1).unwrap();
+        assert!(accrual.settles_management_fee(1_000_000, 0));
+    }
+
+    #[test]
+    fn no_fees_without_shares() {
+        let accrual = accrue(1_000_000, 0, 200, 2_000, PRICE_SCALE, SECONDS_PER_YEAR).unwrap();
+        assert_eq!(accrual.shares, 0);
+        assert_eq!(accrual.management_fee, 0);
+    }
+
+    #[test]
+    fn accrues_at_the_largest_balances() {
+        let accrual = accrue(u64::MAX, u64::MAX / 2, 500, 5_000, PRICE_SCALE, SECONDS_PER_YEAR).unwrap();
+        assert_eq!(accrual.management_fee, u64::MAX / 20);
+        assert!(accrual.shares > 0);
+
+        // Fee shares that do not fit in the supply are an error, not a wrap
+        assert_eq!(
+            accrue(u64::MAX, u64::MAX, 500, 0, PRICE_SCALE, SECONDS_PER_YEAR).unwrap_err(),
+            error!(MyVaultError::MathOverflow)
+        );
+        assert_eq!(
+            accrue(u64::MAX, 1, 500, 0, PRICE_SCALE, u64::MAX).unwrap_err(),
+            error!(MyVaultError::MathOverflow)
+        );
+    }
+}
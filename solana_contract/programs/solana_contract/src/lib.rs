@@ -1,7 +1,16 @@
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program;
+use anchor_lang::solana_program::sysvar::instructions as sysvar_instructions;
 use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
-use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::associated_token::{get_associated_token_address, AssociatedToken};
+
+pub mod adapters;
+pub mod ed25519;
+pub mod fees;
+pub mod math;
+pub mod nav;
+pub mod pyth;
+
+use math::Rounding;
 
 declare_id!("7nsTEo39qMPazWxfPSezWSRMBJ7wTF52dB7JgG3t2X1T");
 
@@ -9,12 +18,29 @@ declare_id!("7nsTEo39qMPazWxfPSezWSRMBJ7wTF52dB7JgG3t2X1T");
 const STRATEGY_SEED: &[u8] = b"strategy";
 const VAULT_SEED: &[u8] = b"vault";
 const BASIS_POINTS: u64 = 10000; // Percentage base, consistent with EVM version
+const MAX_ORACLES: usize = 5; // Maximum registered oracles per strategy
+const MAX_SWAP_PROGRAMS: usize = 4; // Maximum allowlisted swap programs per strategy
+const NONCE_WINDOW: u64 = 64; // Signal nonces accepted out of order behind the newest one
+const MAX_SIGNAL_CLOCK_SKEW: u64 = 30; // Seconds a signal may be issued ahead of the cluster clock
+const PRICE_FEED_SEED: &[u8] = b"price_feed";
+const WITHDRAW_ESCROW_SEED: &[u8] = b"withdraw_escrow";
+const WITHDRAW_REQUEST_SEED: &[u8] = b"withdraw_request";
+const WITHDRAW_EPOCH_SEED: &[u8] = b"withdraw_epoch";
+const POSITION_SEED: &[u8] = b"position";
+const PRICE_SCALE: u128 = 1_000_000_000_000_000_000; // Prices are scaled by 1e18, consistent with EVM PriceOracle
+const DEFAULT_MAX_PRICE_AGE: u64 = 3600; // 1 hour, consistent with EVM PriceOracle
+const DEFAULT_MAX_CONFIDENCE_BPS: u64 = 200; // Pyth confidence interval at most 2% of price
+const DEFAULT_MAX_PYTH_PRICE_AGE: u64 = 60; // Pyth updates are posted with the trade, so far fresher than feeds
+const DEFAULT_MAX_LIQUIDATION_DEVIATION_BPS: u16 = 300; // Liquidation sales lose at most 3% against the oracle price
+const MAX_MANAGEMENT_FEE_BPS: u16 = 500; // 5% a year
+const MAX_PERFORMANCE_FEE_BPS: u16 = 5000; // 50% of gains above the high-water mark
+const MAX_EARLY_EXIT_FEE_BPS: u16 = 1000; // 10% of assets withdrawn during a lock-up
 
 #[program]
 pub mod solana_contract {
     use super::*;
 
-    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+    pub fn initialize(_ctx: Context<Initialize>) -> Result<()> {
         Ok(())
     }
 
@@ -32,6 +58,21 @@ pub mod solana_contract {
         vault.authority = ctx.accounts.authority.key();
         vault.strategy = strategy.key();
         vault.name = name.clone();
+        vault.management_fee_bps = 0;
+        vault.performance_fee_bps = 0;
+        vault.fee_recipient = ctx.accounts.authority.key();
+        vault.high_water_mark = PRICE_SCALE;
+        vault.last_fee_accrual = Clock::get()?.unix_timestamp as u64;
+        vault.withdraw_epoch = 0;
+        vault.pending_withdraw_shares = 0;
+        vault.reserved_assets = 0;
+        vault.investor_access = InvestorAccess::Single;
+        vault.tvl_cap = 0;
+        vault.investor_cap = 0;
+        vault.min_deposit = 0;
+        vault.min_balance = 0;
+        vault.lockup_period = 0;
+        vault.early_exit_fee_bps = 0;
         vault.bump = ctx.bumps.vault;
         
         // Set initial strategy configuration
@@ -41,6 +82,14 @@ pub mod solana_contract {
         strategy.signal_timeout = 900; // 15 minutes, in seconds
         strategy.bump = ctx.bumps.strategy;
         strategy.last_signal_timestamp = 0;
+        // Vault creator is the initial oracle, same as ORACLE_ROLE on EVM
+        strategy.oracles = vec![ctx.accounts.authority.key()];
+        strategy.max_price_age = DEFAULT_MAX_PRICE_AGE;
+        strategy.base_decimals = ctx.accounts.base_token_mint.decimals;
+        strategy.base_pyth_feed_id = None;
+        strategy.max_confidence_bps = DEFAULT_MAX_CONFIDENCE_BPS;
+        strategy.max_pyth_price_age = DEFAULT_MAX_PYTH_PRICE_AGE;
+        strategy.max_liquidation_deviation_bps = DEFAULT_MAX_LIQUIDATION_DEVIATION_BPS;
         
         msg!("Vault initialized: {}", name);
         Ok(())
@@ -85,6 +134,10 @@ pub mod solana_contract {
                 is_active: true,
                 max_allocation,
                 min_exit_amount,
+                venue: SwapVenue::Jupiter,
+                price_source: PriceSource::PriceFeed,
+                token_decimals: ctx.accounts.token_mint.decimals,
+                max_oracle_deviation_bps: 0,
             });
         }
         
@@ -121,6 +174,71 @@ pub mod solana_contract {
         Ok(())
     }
     
+    // Select the swap venue used to trade a pair
+    pub fn set_swap_venue(
+        ctx: Context<SetTradingPair>,
+        venue: SwapVenue,
+    ) -> Result<()> {
+        let strategy = &mut ctx.accounts.strategy;
+        let token_mint = ctx.accounts.token_mint.key();
+        
+        // Check strategy management permission
+        require!(
+            strategy.authority == ctx.accounts.authority.key(),
+            MyVaultError::Unauthorized
+        );
+        
+        let pair = strategy.trading_pairs
+            .iter_mut()
+            .find(|p| p.token_mint == token_mint)
+            .ok_or(MyVaultError::TradingPairNotActive)?;
+        pair.venue = venue;
+        
+        msg!("Swap venue set for {}: {:?}", token_mint, venue);
+        Ok(())
+    }
+    
+    // Select how a pair is priced, and how far trade fills may deviate from
+    // that price (0 disables the guard)
+    pub fn set_price_source(
+        ctx: Context<SetTradingPair>,
+        price_source: PriceSource,
+        max_oracle_deviation_bps: u16,
+    ) -> Result<()> {
+        require!(
+            max_oracle_deviation_bps as u64 <= BASIS_POINTS,
+            MyVaultError::InvalidPercentage
+        );
+        
+        let strategy = &mut ctx.accounts.strategy;
+        let token_mint = ctx.accounts.token_mint.key();
+        
+        // Check strategy management permission
+        require!(
+            strategy.authority == ctx.accounts.authority.key(),
+            MyVaultError::Unauthorized
+        );
+        
+        // A Pyth feed prices exactly one token
+        if let PriceSource::Pyth { feed_id } = price_source {
+            require!(
+                strategy.base_pyth_feed_id != Some(feed_id)
+                    && !strategy.trading_pairs.iter().any(|p| p.token_mint != token_mint && p.price_source == price_source),
+                MyVaultError::PythFeedAlreadyBound
+            );
+        }
+        
+        let pair = strategy.trading_pairs
+            .iter_mut()
+            .find(|p| p.token_mint == token_mint)
+            .ok_or(MyVaultError::TradingPairNotActive)?;
+        pair.price_source = price_source;
+        pair.max_oracle_deviation_bps = max_oracle_deviation_bps;
+        
+        msg!("Price source set for {}: {:?}, max deviation {} bps", token_mint, price_source, max_oracle_deviation_bps);
+        Ok(())
+    }
+    
     // Update strategy settings
     pub fn update_strategy_settings(
         ctx: Context<UpdateStrategy>,
@@ -142,30 +260,344 @@ pub mod solana_contract {
         msg!("Strategy settings updated: Enabled={}, Timeout={} seconds", strategy_enabled, signal_timeout);
         Ok(())
     }
+
+    // Register an oracle allowed to submit trading signals
+    pub fn add_oracle(
+        ctx: Context<UpdateStrategy>,
+        oracle: Pubkey,
+    ) -> Result<()> {
+        let strategy = &mut ctx.accounts.strategy;
+        let vault = &ctx.accounts.vault;
+        
+        // Check vault admin permission
+        require!(
+            strategy.vault == vault.key() && vault.authority == ctx.accounts.authority.key(),
+            MyVaultError::Unauthorized
+        );
+        
+        require!(
+            !strategy.oracles.contains(&oracle),
+            MyVaultError::OracleAlreadyRegistered
+        );
+        require!(
+            strategy.oracles.len() < MAX_ORACLES,
+            MyVaultError::OracleRegistryFull
+        );
+        
+        strategy.oracles.push(oracle);
+        
+        msg!("Oracle added: {}", oracle);
+        Ok(())
+    }
+    
+    // Unregister an oracle
+    pub fn remove_oracle(
+        ctx: Context<UpdateStrategy>,
+        oracle: Pubkey,
+    ) -> Result<()> {
+        let strategy = &mut ctx.accounts.strategy;
+        let vault = &ctx.accounts.vault;
+        
+        // Check vault admin permission
+        require!(
+            strategy.vault == vault.key() && vault.authority == ctx.accounts.authority.key(),
+            MyVaultError::Unauthorized
+        );
+        
+        let index = strategy.oracles
+            .iter()
+            .position(|o| *o == oracle)
+            .ok_or(MyVaultError::OracleNotRegistered)?;
+        strategy.oracles.remove(index);
+        
+        msg!("Oracle removed: {}", oracle);
+        Ok(())
+    }
+    
+    // Set the key whose Ed25519-signed signals any relayer may submit
+    pub fn set_signal_signer(
+        ctx: Context<UpdateStrategy>,
+        signal_signer: Option<Pubkey>,
+    ) -> Result<()> {
+        let strategy = &mut ctx.accounts.strategy;
+        let vault = &ctx.accounts.vault;
+        
+        // Check vault admin permission
+        require!(
+            strategy.vault == vault.key() && vault.authority == ctx.accounts.authority.key(),
+            MyVaultError::Unauthorized
+        );
+        
+        strategy.signal_signer = signal_signer;
+        
+        msg!("Signal signer updated: {:?}", signal_signer);
+        Ok(())
+    }
+    
+    // Allow a swap program (e.g. Jupiter v6) as trade CPI target
+    pub fn add_swap_program(
+        ctx: Context<UpdateStrategy>,
+        swap_program: Pubkey,
+        venue: SwapVenue,
+    ) -> Result<()> {
+        let strategy = &mut ctx.accounts.strategy;
+        let vault = &ctx.accounts.vault;
+        
+        // Check vault admin permission
+        require!(
+            strategy.vault == vault.key() && vault.authority == ctx.accounts.authority.key(),
+            MyVaultError::Unauthorized
+        );
+        
+        require!(
+            !strategy.swap_programs.iter().any(|p| p.program_id == swap_program),
+            MyVaultError::SwapProgramAlreadyAllowed
+        );
+        require!(
+            strategy.swap_programs.len() < MAX_SWAP_PROGRAMS,
+            MyVaultError::SwapProgramListFull
+        );
+        
+        strategy.swap_programs.push(SwapProgram {
+            program_id: swap_program,
+            venue,
+        });
+        
+        msg!("Swap program allowed: {}, Venue={:?}", swap_program, venue);
+        Ok(())
+    }
+    
+    // Remove a swap program from the allowlist
+    pub fn remove_swap_program(
+        ctx: Context<UpdateStrategy>,
+        swap_program: Pubkey,
+    ) -> Result<()> {
+        let strategy = &mut ctx.accounts.strategy;
+        let vault = &ctx.accounts.vault;
+        
+        // Check vault admin permission
+        require!(
+            strategy.vault == vault.key() && vault.authority == ctx.accounts.authority.key(),
+            MyVaultError::Unauthorized
+        );
+        
+        let index = strategy.swap_programs
+            .iter()
+            .position(|p| p.program_id == swap_program)
+            .ok_or(MyVaultError::SwapProgramNotAllowed)?;
+        strategy.swap_programs.remove(index);
+        
+        msg!("Swap program removed: {}", swap_program);
+        Ok(())
+    }
+    
+    // Set how long a price feed update stays valid
+    pub fn set_max_price_age(
+        ctx: Context<UpdateStrategy>,
+        max_price_age: u64,
+    ) -> Result<()> {
+        let strategy = &mut ctx.accounts.strategy;
+        let vault = &ctx.accounts.vault;
+        
+        // Check vault admin permission
+        require!(
+            strategy.vault == vault.key() && vault.authority == ctx.accounts.authority.key(),
+            MyVaultError::Unauthorized
+        );
+        
+        strategy.max_price_age = max_price_age;
+        
+        msg!("Max price age updated: {} seconds", max_price_age);
+        Ok(())
+    }
+    
+    // Set the Pyth feed of the base token, the widest accepted Pyth
+    // confidence interval and how old a Pyth update may be. Without a base
+    // feed, Pyth pair prices are read as quoted in the base token.
+    pub fn set_pyth_settings(
+        ctx: Context<UpdateStrategy>,
+        base_feed_id: Option<[u8; 32]>,
+        max_confidence_bps: u64,
+        max_pyth_price_age: u64,
+    ) -> Result<()> {
+        let strategy = &mut ctx.accounts.strategy;
+        let vault = &ctx.accounts.vault;
+        
+        // Check vault admin permission
+        require!(
+            strategy.vault == vault.key() && vault.authority == ctx.accounts.authority.key(),
+            MyVaultError::Unauthorized
+        );
+        require!(
+            max_confidence_bps <= BASIS_POINTS,
+            MyVaultError::InvalidPercentage
+        );
+        
+        // A base feed may not also price a pair
+        if let Some(feed_id) = base_feed_id {
+            require!(
+                !strategy.trading_pairs.iter().any(|p| p.price_source == PriceSource::Pyth { feed_id }),
+                MyVaultError::PythFeedAlreadyBound
+            );
+        }
+        
+        strategy.base_pyth_feed_id = base_feed_id;
+        strategy.max_confidence_bps = max_confidence_bps;
+        strategy.max_pyth_price_age = max_pyth_price_age;
+        
+        msg!(
+            "Pyth settings updated: base feed {:?}, max confidence {} bps, max age {} seconds",
+            base_feed_id,
+            max_confidence_bps,
+            max_pyth_price_age
+        );
+        Ok(())
+    }
+    
+    // Set how far liquidation sales in redeem_with_liquidation may deviate
+    // from the oracle price
+    pub fn set_max_liquidation_deviation(
+        ctx: Context<UpdateStrategy>,
+        max_liquidation_deviation_bps: u16,
+    ) -> Result<()> {
+        let strategy = &mut ctx.accounts.strategy;
+        let vault = &ctx.accounts.vault;
+        
+        // Check vault admin permission
+        require!(
+            strategy.vault == vault.key() && vault.authority == ctx.accounts.authority.key(),
+            MyVaultError::Unauthorized
+        );
+        require!(
+            max_liquidation_deviation_bps as u64 <= BASIS_POINTS,
+            MyVaultError::InvalidPercentage
+        );
+        
+        strategy.max_liquidation_deviation_bps = max_liquidation_deviation_bps;
+        
+        msg!("Max liquidation deviation updated: {} bps", max_liquidation_deviation_bps);
+        Ok(())
+    }
+    
+    // Create the price feed of a token in the vault base token
+    pub fn initialize_price_feed(ctx: Context<InitializePriceFeed>) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+        
+        // Check vault admin permission
+        require!(
+            vault.authority == ctx.accounts.authority.key(),
+            MyVaultError::Unauthorized
+        );
+        
+        let price_feed = &mut ctx.accounts.price_feed;
+        price_feed.token_mint = ctx.accounts.token_mint.key();
+        price_feed.base_mint = vault.base_token_mint;
+        price_feed.price = 0;
+        price_feed.last_update_timestamp = 0;
+        price_feed.bump = ctx.bumps.price_feed;
+        
+        msg!("Price feed initialized: {}", price_feed.token_mint);
+        Ok(())
+    }
+    
+    // Update a token price, 1 token unit = price / 1e18 base token units
+    pub fn update_price(
+        ctx: Context<UpdatePrice>,
+        price: u128,
+    ) -> Result<()> {
+        // Check oracle permission
+        require!(
+            ctx.accounts.strategy.oracles.contains(&ctx.accounts.oracle.key()),
+            MyVaultError::OracleNotRegistered
+        );
+        
+        let now = Clock::get()?.unix_timestamp as u64;
+        ctx.accounts.price_feed.set_price(price, now)
+    }
+    
+    // Update several token prices, remaining accounts are the price feeds in
+    // the same order as prices
+    pub fn update_prices_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, UpdatePricesBatch<'info>>,
+        prices: Vec<u128>,
+    ) -> Result<()> {
+        // Check oracle permission
+        require!(
+            ctx.accounts.strategy.oracles.contains(&ctx.accounts.oracle.key()),
+            MyVaultError::OracleNotRegistered
+        );
+        
+        require!(
+            !prices.is_empty() && prices.len() == ctx.remaining_accounts.len(),
+            MyVaultError::PriceBatchMismatch
+        );
+        
+        let now = Clock::get()?.unix_timestamp as u64;
+        for (account, price) in ctx.remaining_accounts.iter().zip(prices) {
+            let mut price_feed = Account::<PriceFeed>::try_from(account)?;
+            require_keys_eq!(
+                price_feed.base_mint,
+                ctx.accounts.vault.base_token_mint,
+                MyVaultError::InvalidPriceFeed
+            );
+            price_feed.set_price(price, now)?;
+            price_feed.exit(&crate::ID)?;
+        }
+        
+        Ok(())
+    }
     
     // Modify execute_buy_signal function
     pub fn execute_buy_signal(
         ctx: Context<ExecuteTrade>,
         amount_to_swap: u64,
-        _min_amount_out: u64,
+        min_amount_out: u64,
         max_allocation_pct: u64,
-        jupiter_route_data: Vec<u8>,
+        issued_at: u64,
+        nonce: u64,
+        route_data: Vec<u8>,
     ) -> Result<()> {
         let strategy = &mut ctx.accounts.strategy;
         let vault = &ctx.accounts.vault;
         let token_mint = ctx.accounts.token_mint.key();
         
+        // Verify signal comes from a registered oracle or the strategy signal signer
+        let signer = strategy.authorize_signal(
+            &ctx.accounts.oracle.key(),
+            &ctx.accounts.instructions,
+            &SignalMessage {
+                vault: vault.key(),
+                token_mint,
+                signal_type: SignalType::Buy,
+                amount: amount_to_swap,
+                min_amount_out,
+                issued_at,
+                nonce,
+            },
+        )?;
+        
         // Verify strategy settings
         require!(
             strategy.strategy_enabled,
             MyVaultError::StrategyNotEnabled
         );
         
+        // Verify swap program is allowlisted
+        let swap_program = strategy.allowed_swap_program(&ctx.accounts.swap_program.key())?;
+        
+        // Reject stale signals
+        let now = Clock::get()?.unix_timestamp as u64;
+        strategy.check_signal_age(issued_at, now)?;
+        
+        // Reject replayed signals
+        strategy.consume_nonce(nonce)?;
+        
         // Verify trading pair
-        let trading_pair = strategy.trading_pairs
+        let trading_pair = *strategy.trading_pairs
             .iter()
             .find(|p| p.token_mint == token_mint && p.is_active)
             .ok_or(MyVaultError::TradingPairNotActive)?;
+        let venue = trading_pair.venue;
         
         require!(
             max_allocation_pct <= trading_pair.max_allocation,
@@ -173,23 +605,26 @@ pub mod solana_contract {
         );
         
         // Get vault base balance
-        let vault_base_balance = ctx.accounts.vault_base_token.amount;
+        let vault_base_balance = ctx.accounts.vault.available_base(ctx.accounts.vault_base_token.amount);
         
         // Verify swap amount does not exceed allocation
-        let max_allowed_amount = (vault_base_balance * max_allocation_pct) / BASIS_POINTS;
+        let max_allowed_amount = math::bps(vault_base_balance, max_allocation_pct, Rounding::Down)?;
         require!(
             amount_to_swap <= max_allowed_amount,
             MyVaultError::SwapAmountExceedsAllocation
         );
         
         // Update last signal timestamp
-        strategy.last_signal_timestamp = Clock::get()?.unix_timestamp as u64;
+        strategy.last_signal_timestamp = now;
         
         // Emit event
         emit!(SignalReceived {
             signal_type: SignalType::Buy,
             token_mint,
             timestamp: strategy.last_signal_timestamp,
+            issued_at,
+            signer,
+            nonce,
         });
         
         // If swap amount is zero, return immediately
@@ -197,62 +632,78 @@ pub mod solana_contract {
             return Ok(());
         }
         
-        // Create temporary signer PDA to authorize transfer
+        // Verify the route swaps exactly what the signal asks for
+        adapters::validate_route(
+            venue,
+            &swap_program,
+            &route_data,
+            ctx.remaining_accounts,
+            &adapters::ExpectedSwap {
+                transfer_authority: ctx.accounts.vault_authority.key(),
+                source_token_account: ctx.accounts.vault_base_token.key(),
+                destination_token_account: ctx.accounts.vault_token_account.key(),
+                source_mint: vault.base_token_mint,
+                destination_mint: token_mint,
+                amount_in: amount_to_swap,
+                min_amount_out,
+            },
+        )?;
+        
+        // Snapshot balances so the swap can be measured exactly
+        let input_before = ctx.accounts.vault_base_token.amount;
+        let output_before = ctx.accounts.vault_token_account.amount;
+        
+        // Swap with the vault authority PDA as the signing user
         let vault_authority_seeds = &[
             VAULT_SEED,
             vault.base_token_mint.as_ref(),
             &[ctx.bumps.vault_authority],
         ];
         
-        // Transfer tokens from vault to Jupiter program
-        if amount_to_swap > 0 {
-            // We transfer tokens from vault to our temporary account, then call Jupiter for swap
-            let cpi_accounts = Transfer {
-                from: ctx.accounts.vault_base_token.to_account_info(),
-                to: ctx.accounts.jupiter_user_token_account.to_account_info(),
-                authority: ctx.accounts.vault_authority.to_account_info(),
-            };
-            
-            let cpi_program = ctx.accounts.token_program.to_account_info();
-            let cpi_signer_seeds = &[&vault_authority_seeds[..]];
-            
-            let cpi_ctx = CpiContext::new_with_signer(
-                cpi_program,
-                cpi_accounts,
-                cpi_signer_seeds,
-            );
-            
-            token::transfer(cpi_ctx, amount_to_swap)?;
-            
-            // Call Jupiter to execute swap
-            let jupiter_program = ctx.accounts.jupiter_program.to_account_info();
-            let accounts_vec: Vec<AccountInfo> = ctx.remaining_accounts.to_vec();
-            let route_instruction = anchor_lang::solana_program::instruction::Instruction {
-                program_id: jupiter_program.key(),
-                accounts: ctx.remaining_accounts.iter().map(|a| {
-                    anchor_lang::solana_program::instruction::AccountMeta {
-                        pubkey: a.key(),
-                        is_signer: a.is_signer,
-                        is_writable: a.is_writable,
-                    }
-                }).collect(),
-                data: jupiter_route_data,
-            };
-            
-            anchor_lang::solana_program::program::invoke(
-                &route_instruction,
-                &accounts_vec[..],
+        invoke_swap_route(
+            ctx.accounts.swap_program.key(),
+            ctx.accounts.vault_authority.key(),
+            ctx.remaining_accounts,
+            route_data,
+            &vault_authority_seeds[..],
+        )?;
+        
+        // Measure what actually left and arrived in the vault
+        ctx.accounts.vault_base_token.reload()?;
+        ctx.accounts.vault_token_account.reload()?;
+        let (amount_in, amount_out) = measure_swap(
+            input_before,
+            ctx.accounts.vault_base_token.amount,
+            output_before,
+            ctx.accounts.vault_token_account.amount,
+            amount_to_swap,
+            min_amount_out,
+        )?;
+        
+        // Check the fill against the pair's oracle price
+        if trading_pair.max_oracle_deviation_bps > 0 {
+            let received_value = ctx.accounts.strategy.pair_value(
+                vault,
+                &trading_pair,
+                amount_out,
+                ctx.accounts.price_source.as_deref(),
+                ctx.accounts.base_price_source.as_deref(),
+                now,
             )?;
-            
-            // Record event
-            emit!(TradeExecuted {
-                signal_type: SignalType::Buy,
-                token_mint,
-                amount: amount_to_swap,
-                result: ctx.accounts.vault_token_account.amount, // This is an approximation, subsequent use can use a more accurate method
-            });
+            check_oracle_deviation(amount_in, received_value, trading_pair.max_oracle_deviation_bps)?;
         }
         
+        // Record event
+        emit!(TradeExecuted {
+            signal_type: SignalType::Buy,
+            token_mint,
+            input_mint: vault.base_token_mint,
+            output_mint: token_mint,
+            amount: amount_in,
+            result: amount_out,
+            nonce,
+        });
+        
         Ok(())
     }
     
@@ -261,23 +712,51 @@ pub mod solana_contract {
         ctx: Context<ExecuteTrade>,
         amount_to_sell: u64,
         min_amount_out: u64,
-        jupiter_route_data: Vec<u8>,
+        issued_at: u64,
+        nonce: u64,
+        route_data: Vec<u8>,
     ) -> Result<()> {
         let strategy = &mut ctx.accounts.strategy;
         let vault = &ctx.accounts.vault;
         let token_mint = ctx.accounts.token_mint.key();
         
+        // Verify signal comes from a registered oracle or the strategy signal signer
+        let signer = strategy.authorize_signal(
+            &ctx.accounts.oracle.key(),
+            &ctx.accounts.instructions,
+            &SignalMessage {
+                vault: vault.key(),
+                token_mint,
+                signal_type: SignalType::Sell,
+                amount: amount_to_sell,
+                min_amount_out,
+                issued_at,
+                nonce,
+            },
+        )?;
+        
         // Verify strategy settings
         require!(
             strategy.strategy_enabled,
             MyVaultError::StrategyNotEnabled
         );
         
+        // Verify swap program is allowlisted
+        let swap_program = strategy.allowed_swap_program(&ctx.accounts.swap_program.key())?;
+        
+        // Reject stale signals
+        let now = Clock::get()?.unix_timestamp as u64;
+        strategy.check_signal_age(issued_at, now)?;
+        
+        // Reject replayed signals
+        strategy.consume_nonce(nonce)?;
+        
         // Verify trading pair
-        let trading_pair = strategy.trading_pairs
+        let trading_pair = *strategy.trading_pairs
             .iter()
             .find(|p| p.token_mint == token_mint && p.is_active)
             .ok_or(MyVaultError::TradingPairNotActive)?;
+        let venue = trading_pair.venue;
         
         // Get token balance in vault
         let token_balance = ctx.accounts.vault_token_account.amount;
@@ -294,136 +773,328 @@ pub mod solana_contract {
             amount_to_sell
         };
         
+        // Don't leave a position below the minimum exit amount
+        let sell_amount = trading_pair.exit_amount(sell_amount, token_balance)?;
+        
         // Update last signal timestamp
-        strategy.last_signal_timestamp = Clock::get()?.unix_timestamp as u64;
+        strategy.last_signal_timestamp = now;
         
         // Emit event
         emit!(SignalReceived {
             signal_type: SignalType::Sell,
             token_mint,
             timestamp: strategy.last_signal_timestamp,
+            issued_at,
+            signer,
+            nonce,
         });
         
-        // Create temporary signer PDA to authorize transfer
+        // Verify the route swaps exactly what the signal asks for
+        adapters::validate_route(
+            venue,
+            &swap_program,
+            &route_data,
+            ctx.remaining_accounts,
+            &adapters::ExpectedSwap {
+                transfer_authority: ctx.accounts.vault_authority.key(),
+                source_token_account: ctx.accounts.vault_token_account.key(),
+                destination_token_account: ctx.accounts.vault_base_token.key(),
+                source_mint: token_mint,
+                destination_mint: vault.base_token_mint,
+                amount_in: sell_amount,
+                min_amount_out,
+            },
+        )?;
+        
+        // Snapshot balances so the swap can be measured exactly
+        let input_before = ctx.accounts.vault_token_account.amount;
+        let output_before = ctx.accounts.vault_base_token.amount;
+        
+        // Swap with the vault authority PDA as the signing user
         let vault_authority_seeds = &[
             VAULT_SEED,
             vault.base_token_mint.as_ref(),
             &[ctx.bumps.vault_authority],
         ];
         
-        // Transfer tokens from vault to Jupiter program
-        if sell_amount > 0 {
-            // We transfer tokens from vault to our temporary account, then call Jupiter for swap
-            let cpi_accounts = Transfer {
-                from: ctx.accounts.vault_token_account.to_account_info(),
-                to: ctx.accounts.jupiter_user_token_account.to_account_info(),
-                authority: ctx.accounts.vault_authority.to_account_info(),
-            };
-            
-            let cpi_program = ctx.accounts.token_program.to_account_info();
-            let cpi_signer_seeds = &[&vault_authority_seeds[..]];
-            
-            let cpi_ctx = CpiContext::new_with_signer(
-                cpi_program,
-                cpi_accounts,
-                cpi_signer_seeds,
-            );
-            
-            token::transfer(cpi_ctx, sell_amount)?;
-            
-            // Call Jupiter to execute swap
-            let jupiter_program = ctx.accounts.jupiter_program.to_account_info();
-            let accounts_vec: Vec<AccountInfo> = ctx.remaining_accounts.to_vec();
-            let route_instruction = anchor_lang::solana_program::instruction::Instruction {
-                program_id: jupiter_program.key(),
-                accounts: ctx.remaining_accounts.iter().map(|a| {
-                    anchor_lang::solana_program::instruction::AccountMeta {
-                        pubkey: a.key(),
-                        is_signer: a.is_signer,
-                        is_writable: a.is_writable,
-                    }
-                }).collect(),
-                data: jupiter_route_data,
-            };
-            
-            anchor_lang::solana_program::program::invoke(
-                &route_instruction,
-                &accounts_vec[..],
+        invoke_swap_route(
+            ctx.accounts.swap_program.key(),
+            ctx.accounts.vault_authority.key(),
+            ctx.remaining_accounts,
+            route_data,
+            &vault_authority_seeds[..],
+        )?;
+        
+        // Measure what actually left and arrived in the vault
+        ctx.accounts.vault_token_account.reload()?;
+        ctx.accounts.vault_base_token.reload()?;
+        let (amount_in, amount_out) = measure_swap(
+            input_before,
+            ctx.accounts.vault_token_account.amount,
+            output_before,
+            ctx.accounts.vault_base_token.amount,
+            sell_amount,
+            min_amount_out,
+        )?;
+        
+        // Check the fill against the pair's oracle price
+        if trading_pair.max_oracle_deviation_bps > 0 {
+            let spent_value = ctx.accounts.strategy.pair_value(
+                vault,
+                &trading_pair,
+                amount_in,
+                ctx.accounts.price_source.as_deref(),
+                ctx.accounts.base_price_source.as_deref(),
+                now,
             )?;
-            
-            // Record event
-            emit!(TradeExecuted {
-                signal_type: SignalType::Sell,
-                token_mint,
-                amount: sell_amount,
-                result: ctx.accounts.vault_base_token.amount, // This is an approximation, subsequent use can use a more accurate method
-            });
+            check_oracle_deviation(spent_value, amount_out, trading_pair.max_oracle_deviation_bps)?;
         }
         
+        // Record event
+        emit!(TradeExecuted {
+            signal_type: SignalType::Sell,
+            token_mint,
+            input_mint: token_mint,
+            output_mint: vault.base_token_mint,
+            amount: amount_in,
+            result: amount_out,
+            nonce,
+        });
+        
         Ok(())
     }
 
-    // Deposit base assets
-    pub fn deposit(
-        ctx: Context<Deposit>,
-        amount: u64,
+    // Swap between any two of the base token and active trading pairs
+    pub fn execute_swap(
+        ctx: Context<ExecuteSwap>,
+        amount_in: u64,
+        min_amount_out: u64,
+        issued_at: u64,
+        nonce: u64,
+        route_data: Vec<u8>,
     ) -> Result<()> {
+        let strategy = &mut ctx.accounts.strategy;
         let vault = &ctx.accounts.vault;
+        let input_mint = ctx.accounts.input_mint.key();
+        let output_mint = ctx.accounts.output_mint.key();
         
-        // Check if it's specified investor
+        // Verify signal comes from a registered oracle or the strategy signal signer
+        let signer = strategy.authorize_signal(
+            &ctx.accounts.oracle.key(),
+            &ctx.accounts.instructions,
+            &SwapSignalMessage {
+                vault: vault.key(),
+                input_mint,
+                output_mint,
+                amount: amount_in,
+                min_amount_out,
+                issued_at,
+                nonce,
+            },
+        )?;
+        
+        // Verify strategy settings
         require!(
-            vault.investor == ctx.accounts.authority.key(),
-            MyVaultError::OnlyInvestorAllowed
+            strategy.strategy_enabled,
+            MyVaultError::StrategyNotEnabled
         );
         
-        // Calculate shares to mint
-        let shares_to_mint = if ctx.accounts.vault_shares.supply == 0 {
-            // First deposit, 1:1 mint
-            amount
+        // Verify swap program is allowlisted
+        let swap_program = strategy.allowed_swap_program(&ctx.accounts.swap_program.key())?;
+        
+        // Reject stale signals
+        let now = Clock::get()?.unix_timestamp as u64;
+        strategy.check_signal_age(issued_at, now)?;
+        
+        // Reject replayed signals
+        strategy.consume_nonce(nonce)?;
+        
+        require!(input_mint != output_mint, MyVaultError::SameSwapMints);
+        
+        // Each side must be the base token or an active trading pair. The route
+        // goes through the input pair's venue, or the output pair's when buying.
+        let base_mint = vault.base_token_mint;
+        let active_pair = |mint: &Pubkey| {
+            strategy.trading_pairs
+                .iter()
+                .find(|p| p.token_mint == *mint && p.is_active)
+                .copied()
+                .ok_or(MyVaultError::TradingPairNotActive)
+        };
+        let input_pair = if input_mint == base_mint { None } else { Some(active_pair(&input_mint)?) };
+        let output_pair = if output_mint == base_mint { None } else { Some(active_pair(&output_mint)?) };
+        let venue = input_pair.or(output_pair).ok_or(MyVaultError::SameSwapMints)?.venue;
+        
+        // Swaps must use the same vault token accounts as buys and sells
+        let vault_authority = ctx.accounts.vault_authority.key();
+        check_vault_token_account(vault, &vault_authority, &input_mint, &ctx.accounts.vault_input_token.key())?;
+        check_vault_token_account(vault, &vault_authority, &output_mint, &ctx.accounts.vault_output_token.key())?;
+        
+        require!(amount_in > 0, MyVaultError::InvalidSwapAmount);
+        let input_balance = if input_mint == base_mint {
+            vault.available_base(ctx.accounts.vault_input_token.amount)
         } else {
-            // Calculate shares proportionally
-            let total_assets = ctx.accounts.vault_base_token.amount;
-            (amount * ctx.accounts.vault_shares.supply) / total_assets
+            ctx.accounts.vault_input_token.amount
         };
+        require!(
+            amount_in <= input_balance,
+            MyVaultError::InsufficientVaultBalance
+        );
         
-        // Transfer tokens to vault
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.user_token.to_account_info(),
-            to: ctx.accounts.vault_base_token.to_account_info(),
-            authority: ctx.accounts.authority.to_account_info(),
+        // Don't leave an input position below its minimum exit amount
+        let amount_in = match input_pair {
+            Some(pair) => pair.exit_amount(amount_in, input_balance)?,
+            None => amount_in,
         };
         
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(
-            cpi_program,
-            cpi_accounts,
-        );
+        // Swaps into a pair spend at most its max allocation of the base
+        // balance, same as buys. Rotations value the input at its pair price.
+        if let Some(pair) = output_pair {
+            let spent_value = match input_pair {
+                Some(input_pair) => strategy.pair_value(
+                    vault,
+                    &input_pair,
+                    amount_in,
+                    ctx.accounts.price_source.as_deref(),
+                    ctx.accounts.base_price_source.as_deref(),
+                    now,
+                )?,
+                None => amount_in,
+            };
+            let vault_base_balance = vault.available_base(ctx.accounts.vault_base_token.amount);
+            let max_allowed_amount = math::bps(vault_base_balance, pair.max_allocation, Rounding::Down)?;
+            require!(
+                spent_value <= max_allowed_amount,
+                MyVaultError::SwapAmountExceedsAllocation
+            );
+        }
+        
+        // Same signal type and token as the EVM vault reports
+        let (signal_type, token_mint) = if input_mint == base_mint {
+            (SignalType::Buy, output_mint)
+        } else {
+            (SignalType::Sell, input_mint)
+        };
         
-        token::transfer(cpi_ctx, amount)?;
+        // Update last signal timestamp
+        strategy.last_signal_timestamp = now;
         
-        // Mint shares tokens
+        // Emit event
+        emit!(SignalReceived {
+            signal_type,
+            token_mint,
+            timestamp: strategy.last_signal_timestamp,
+            issued_at,
+            signer,
+            nonce,
+        });
+        
+        // Verify the route swaps exactly what the signal asks for
+        adapters::validate_route(
+            venue,
+            &swap_program,
+            &route_data,
+            ctx.remaining_accounts,
+            &adapters::ExpectedSwap {
+                transfer_authority: vault_authority,
+                source_token_account: ctx.accounts.vault_input_token.key(),
+                destination_token_account: ctx.accounts.vault_output_token.key(),
+                source_mint: input_mint,
+                destination_mint: output_mint,
+                amount_in,
+                min_amount_out,
+            },
+        )?;
+        
+        // Snapshot balances so the swap can be measured exactly
+        let input_before = ctx.accounts.vault_input_token.amount;
+        let output_before = ctx.accounts.vault_output_token.amount;
+        
+        // Swap with the vault authority PDA as the signing user
         let vault_authority_seeds = &[
             VAULT_SEED,
-            vault.base_token_mint.as_ref(),
+            base_mint.as_ref(),
             &[ctx.bumps.vault_authority],
         ];
         
-        let cpi_accounts = token::MintTo {
-            mint: ctx.accounts.vault_shares.to_account_info(),
-            to: ctx.accounts.user_shares.to_account_info(),
-            authority: ctx.accounts.vault_authority.to_account_info(),
+        invoke_swap_route(
+            ctx.accounts.swap_program.key(),
+            vault_authority,
+            ctx.remaining_accounts,
+            route_data,
+            &vault_authority_seeds[..],
+        )?;
+        
+        // Measure what actually left and arrived in the vault
+        ctx.accounts.vault_input_token.reload()?;
+        ctx.accounts.vault_output_token.reload()?;
+        let (spent, received) = measure_swap(
+            input_before,
+            ctx.accounts.vault_input_token.amount,
+            output_before,
+            ctx.accounts.vault_output_token.amount,
+            amount_in,
+            min_amount_out,
+        )?;
+        
+        // Check the fill against the oracle price of the traded pair. Rotations
+        // between two pairs have no base side to compare and are not guarded.
+        let guarded_pair = match (input_pair, output_pair) {
+            (Some(pair), None) | (None, Some(pair)) if pair.max_oracle_deviation_bps > 0 => Some(pair),
+            _ => None,
         };
+        if let Some(pair) = guarded_pair {
+            let pair_value = |amount: u64| {
+                ctx.accounts.strategy.pair_value(
+                    vault,
+                    &pair,
+                    amount,
+                    ctx.accounts.price_source.as_deref(),
+                    ctx.accounts.base_price_source.as_deref(),
+                    now,
+                )
+            };
+            let (spent_value, received_value) = if signal_type == SignalType::Buy {
+                (spent, pair_value(received)?)
+            } else {
+                (pair_value(spent)?, received)
+            };
+            check_oracle_deviation(spent_value, received_value, pair.max_oracle_deviation_bps)?;
+        }
         
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_signer_seeds = &[&vault_authority_seeds[..]];
+        // Record event
+        emit!(TradeExecuted {
+            signal_type,
+            token_mint,
+            input_mint,
+            output_mint,
+            amount: spent,
+            result: received,
+            nonce,
+        });
         
-        let cpi_ctx = CpiContext::new_with_signer(
-            cpi_program,
-            cpi_accounts,
-            cpi_signer_seeds,
-        );
+        Ok(())
+    }
+
+    // Deposit base assets
+    pub fn deposit(
+        ctx: Context<Deposit>,
+        amount: u64,
+    ) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+        
+        // Check the investor may deposit
+        vault.check_deposit(&ctx.accounts.authority.key(), &ctx.accounts.position)?;
+        
+        // Calculate shares to mint proportionally to NAV, same as preview_deposit
+        let total_assets = ctx.accounts.total_assets(ctx.remaining_accounts)?;
+        ctx.accounts.accrue_fees(total_assets, ctx.bumps.vault_authority)?;
+        let shares_to_mint = nav::convert_to_shares(amount, total_assets, ctx.accounts.vault_shares.supply, Rounding::Down)?;
+        require!(shares_to_mint > 0, MyVaultError::ZeroShares);
         
-        token::mint_to(cpi_ctx, shares_to_mint)?;
+        ctx.accounts.check_deposit_limits(amount, shares_to_mint, total_assets)?;
+        
+        ctx.accounts.deposit_and_mint(amount, shares_to_mint, ctx.bumps.vault_authority)?;
         
         emit!(Deposited {
             user: ctx.accounts.authority.key(),
@@ -433,80 +1104,80 @@ pub mod solana_contract {
         
         Ok(())
     }
-
-    // Withdraw a certain percentage of assets
-    pub fn percentage_withdraw(
-        ctx: Context<Withdraw>,
-        percentage: u64,
+    
+    // Mint an exact amount of shares, paying at most max_assets_in
+    pub fn mint_shares(
+        ctx: Context<Deposit>,
+        shares: u64,
+        max_assets_in: u64,
     ) -> Result<()> {
         let vault = &ctx.accounts.vault;
         
-        // Check if it's specified investor
+        // Check the investor may deposit
+        vault.check_deposit(&ctx.accounts.authority.key(), &ctx.accounts.position)?;
+        
+        require!(shares > 0, MyVaultError::ZeroShares);
+        
+        // Calculate assets to pay at NAV, same as preview_mint
+        let total_assets = ctx.accounts.total_assets(ctx.remaining_accounts)?;
+        ctx.accounts.accrue_fees(total_assets, ctx.bumps.vault_authority)?;
+        let assets = nav::convert_to_assets(shares, total_assets, ctx.accounts.vault_shares.supply, Rounding::Up)?;
         require!(
-            vault.investor == ctx.accounts.authority.key(),
-            MyVaultError::OnlyInvestorAllowed
+            assets <= max_assets_in,
+            MyVaultError::MaxAssetsInExceeded
         );
         
+        ctx.accounts.check_deposit_limits(assets, shares, total_assets)?;
+        
+        ctx.accounts.deposit_and_mint(assets, shares, ctx.bumps.vault_authority)?;
+        
+        emit!(Deposited {
+            user: ctx.accounts.authority.key(),
+            amount: assets,
+            shares,
+        });
+        
+        Ok(())
+    }
+
+    // Withdraw a certain percentage of assets
+    pub fn percentage_withdraw(
+        ctx: Context<Withdraw>,
+        percentage: u64,
+    ) -> Result<()> {
         require!(
             percentage > 0 && percentage <= BASIS_POINTS,
             MyVaultError::InvalidPercentage
         );
         
-        // Calculate shares to redeem
-        let total_shares = ctx.accounts.user_shares.amount;
-        let shares_to_redeem = (total_shares * percentage) / BASIS_POINTS;
+        let total_assets = ctx.accounts.total_assets(ctx.remaining_accounts)?;
+        ctx.accounts.accrue_fees(total_assets, ctx.bumps.vault_authority)?;
+        
+        // Calculate shares to redeem out of the position
+        let total_shares = ctx.accounts.position.shares;
+        let shares_to_redeem = math::bps(total_shares, percentage, Rounding::Down)?;
         
         // If shares are 0, return immediately
         if shares_to_redeem == 0 {
             return Ok(());
         }
         
-        // Calculate assets to withdraw
-        let total_assets = ctx.accounts.vault_base_token.amount;
-        let assets_to_withdraw = (shares_to_redeem * total_assets) / ctx.accounts.vault_shares.supply;
-        
-        // Ensure vault has enough base assets
-        // Note: In actual implementation, this may need to call an auxiliary function to sell other tokens to get base assets
-        
-        // Burn shares tokens
-        let vault_authority_seeds = &[
-            VAULT_SEED,
-            vault.base_token_mint.as_ref(),
-            &[ctx.bumps.vault_authority],
-        ];
+        // Calculate assets to withdraw at NAV
+        let assets_to_withdraw = nav::convert_to_assets(shares_to_redeem, total_assets, ctx.accounts.vault_shares.supply, Rounding::Down)?;
         
-        // First burn shares
-        let cpi_accounts = token::Burn {
-            mint: ctx.accounts.vault_shares.to_account_info(),
-            from: ctx.accounts.user_shares.to_account_info(),
-            authority: ctx.accounts.authority.to_account_info(),
-        };
+        // Withdrawals during the lock-up leave an early exit fee in the vault
+        let assets_to_withdraw = ctx.accounts.assets_after_early_exit_fee(assets_to_withdraw)?;
         
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(
-            cpi_program,
-            cpi_accounts,
+        // Ensure vault has enough base assets, redeem_with_liquidation sells
+        // other tokens when it does not
+        require!(
+            assets_to_withdraw <= ctx.accounts.vault.available_base(ctx.accounts.vault_base_token.amount),
+            MyVaultError::InsufficientVaultBalance
         );
         
-        token::burn(cpi_ctx, shares_to_redeem)?;
-        
-        // Transfer assets to user
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.vault_base_token.to_account_info(),
-            to: ctx.accounts.user_token.to_account_info(),
-            authority: ctx.accounts.vault_authority.to_account_info(),
-        };
-        
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_signer_seeds = &[&vault_authority_seeds[..]];
-        
-        let cpi_ctx = CpiContext::new_with_signer(
-            cpi_program,
-            cpi_accounts,
-            cpi_signer_seeds,
-        );
+        ctx.accounts.check_remaining_balance(shares_to_redeem, total_assets)?;
         
-        token::transfer(cpi_ctx, assets_to_withdraw)?;
+        ctx.accounts.burn_and_pay(shares_to_redeem, assets_to_withdraw, ctx.bumps.vault_authority)?;
         
         emit!(Withdrawn {
             user: ctx.accounts.authority.key(),
@@ -522,324 +1193,2703 @@ pub mod solana_contract {
         ctx: Context<Withdraw>,
         amount: u64,
     ) -> Result<()> {
-        let vault = &ctx.accounts.vault;
+        require!(amount > 0, MyVaultError::InvalidWithdrawAmount);
+        
+        // Calculate shares to burn at NAV, rounded up like preview_withdraw,
+        // including any early exit fee left in the vault
+        let total_assets = ctx.accounts.total_assets(ctx.remaining_accounts)?;
+        ctx.accounts.accrue_fees(total_assets, ctx.bumps.vault_authority)?;
+        let early_exit_fee = ctx.accounts.early_exit_fee(amount)?;
+        let shares_to_burn = nav::convert_to_shares(
+            amount.checked_add(early_exit_fee).ok_or(MyVaultError::MathOverflow)?,
+            total_assets,
+            ctx.accounts.vault_shares.supply,
+            Rounding::Up,
+        )?;
+        
+        // Ensure the position has enough shares
+        require!(
+            shares_to_burn <= ctx.accounts.position.shares,
+            MyVaultError::InsufficientShares
+        );
         
-        // Check if it's specified investor
+        // Ensure vault has enough base assets
         require!(
-            vault.investor == ctx.accounts.authority.key(),
-            MyVaultError::OnlyInvestorAllowed
+            amount <= ctx.accounts.vault.available_base(ctx.accounts.vault_base_token.amount),
+            MyVaultError::InsufficientVaultBalance
         );
         
-        require!(amount > 0, MyVaultError::InvalidWithdrawAmount);
+        ctx.accounts.check_remaining_balance(shares_to_burn, total_assets)?;
+        
+        ctx.accounts.burn_and_pay(shares_to_burn, amount, ctx.bumps.vault_authority)?;
+        
+        emit!(Withdrawn {
+            user: ctx.accounts.authority.key(),
+            amount,
+            shares: shares_to_burn,
+        });
+        
+        Ok(())
+    }
+    
+    // Redeem an exact amount of shares for at least min_assets_out
+    pub fn redeem(
+        ctx: Context<Withdraw>,
+        shares: u64,
+        min_assets_out: u64,
+    ) -> Result<()> {
+        require!(shares > 0, MyVaultError::InvalidWithdrawAmount);
         
-        // Calculate shares to burn
-        let total_assets = ctx.accounts.vault_base_token.amount;
-        let shares_to_burn = (amount * ctx.accounts.vault_shares.supply) / total_assets;
+        let total_assets = ctx.accounts.total_assets(ctx.remaining_accounts)?;
+        ctx.accounts.accrue_fees(total_assets, ctx.bumps.vault_authority)?;
         
-        // Ensure user has enough shares
+        // Ensure the position has enough shares
         require!(
-            shares_to_burn <= ctx.accounts.user_shares.amount,
+            shares <= ctx.accounts.position.shares,
             MyVaultError::InsufficientShares
         );
         
+        // Calculate assets to pay at NAV, same as preview_redeem, less any
+        // early exit fee
+        let assets = nav::convert_to_assets(shares, total_assets, ctx.accounts.vault_shares.supply, Rounding::Down)?;
+        let assets = ctx.accounts.assets_after_early_exit_fee(assets)?;
+        require!(
+            assets >= min_assets_out,
+            MyVaultError::MinAssetsOutNotMet
+        );
+        
         // Ensure vault has enough base assets
         require!(
-            amount <= ctx.accounts.vault_base_token.amount,
+            assets <= ctx.accounts.vault.available_base(ctx.accounts.vault_base_token.amount),
             MyVaultError::InsufficientVaultBalance
         );
         
-        // Burn shares tokens
+        ctx.accounts.check_remaining_balance(shares, total_assets)?;
+        
+        ctx.accounts.burn_and_pay(shares, assets, ctx.bumps.vault_authority)?;
+        
+        emit!(Withdrawn {
+            user: ctx.accounts.authority.key(),
+            amount: assets,
+            shares,
+        });
+        
+        Ok(())
+    }
+    
+    // Redeem shares, selling non-base positions when the base balance cannot
+    // cover the payout. Like _ensureSufficientLiquidity on EVM, the shortfall
+    // is raised from each position pro rata to its value. Remaining accounts
+    // are the NAV accounts followed by, for each route, the swap program and
+    // the route's venue accounts.
+    pub fn redeem_with_liquidation(
+        ctx: Context<Withdraw>,
+        shares: u64,
+        min_assets_out: u64,
+        routes: Vec<LiquidationRoute>,
+    ) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+        let strategy = &ctx.accounts.strategy;
+        
+        require!(shares > 0, MyVaultError::InvalidWithdrawAmount);
+        
+        // Value positions and calculate assets to pay at NAV
+        let vault_authority = ctx.accounts.vault_authority.key();
+        let now = Clock::get()?.unix_timestamp as u64;
+        let (nav_accounts, mut route_accounts) = ctx.remaining_accounts
+            .split_at_checked(nav::nav_accounts_len(strategy, vault))
+            .ok_or(MyVaultError::InvalidNavAccounts)?;
+        let positions = nav::positions(strategy, vault, &vault_authority, nav_accounts, now)?;
+        let base_balance = vault.available_base(ctx.accounts.vault_base_token.amount);
+        let non_base_value = positions
+            .iter()
+            .try_fold(0u64, |total, position| total.checked_add(position.value))
+            .ok_or(MyVaultError::MathOverflow)?;
+        let total_assets = base_balance
+            .checked_add(non_base_value)
+            .ok_or(MyVaultError::MathOverflow)?;
+        ctx.accounts.accrue_fees(total_assets, ctx.bumps.vault_authority)?;
+        require!(
+            shares <= ctx.accounts.position.shares,
+            MyVaultError::InsufficientShares
+        );
+        let assets = nav::convert_to_assets(shares, total_assets, ctx.accounts.vault_shares.supply, Rounding::Down)?;
+        let early_exit_fee = ctx.accounts.early_exit_fee(assets)?;
+        let vault = &ctx.accounts.vault;
+        let strategy = &ctx.accounts.strategy;
+        
+        // Sell each position's part of the shortfall, rounded up so the sales
+        // cover it. Routes may sell more than required only to exit a position
+        // that would be left below its minimum exit amount.
+        let shortfall = assets.saturating_sub(base_balance);
         let vault_authority_seeds = &[
             VAULT_SEED,
             vault.base_token_mint.as_ref(),
             &[ctx.bumps.vault_authority],
         ];
+        let mut routes = routes.into_iter();
+        let mut slippage = 0u64;
+        for position in positions.iter().filter(|p| shortfall > 0 && p.value > 0) {
+            let required_value = math::mul_div_u64(shortfall, position.value, non_base_value, Rounding::Up)?;
+            let required_amount = math::mul_div_u64(position.balance, required_value, position.value, Rounding::Up)?
+                .min(position.balance);
+            let exit_amount = position.pair.exit_amount(required_amount, position.balance)?;
+            
+            let route = routes.next().ok_or(MyVaultError::LiquidationRouteMismatch)?;
+            require!(
+                route.token_mint == position.pair.token_mint
+                    && route.amount_in >= required_amount
+                    && route.amount_in <= exit_amount,
+                MyVaultError::LiquidationRouteMismatch
+            );
+            
+            // Split off the route's swap program and venue accounts
+            let (swap_program, rest) = route_accounts
+                .split_first()
+                .ok_or(MyVaultError::LiquidationRouteMismatch)?;
+            let (venue_accounts, rest) = rest
+                .split_at_checked(route.accounts_len as usize)
+                .ok_or(MyVaultError::LiquidationRouteMismatch)?;
+            route_accounts = rest;
+            
+            // Verify swap program is allowlisted
+            require!(swap_program.executable, MyVaultError::SwapProgramNotAllowed);
+            let allowed_program = strategy.allowed_swap_program(swap_program.key)?;
+            
+            // Verify the route sells exactly the route amount into the base token
+            adapters::validate_route(
+                position.pair.venue,
+                &allowed_program,
+                &route.data,
+                venue_accounts,
+                &adapters::ExpectedSwap {
+                    transfer_authority: vault_authority,
+                    source_token_account: position.token_account.key(),
+                    destination_token_account: ctx.accounts.vault_base_token.key(),
+                    source_mint: position.pair.token_mint,
+                    destination_mint: vault.base_token_mint,
+                    amount_in: route.amount_in,
+                    min_amount_out: route.min_amount_out,
+                },
+            )?;
+            
+            let output_before = ctx.accounts.vault_base_token.amount;
+            invoke_swap_route(
+                swap_program.key(),
+                vault_authority,
+                venue_accounts,
+                route.data,
+                &vault_authority_seeds[..],
+            )?;
+            
+            // Measure what actually left and arrived in the vault
+            ctx.accounts.vault_base_token.reload()?;
+            let (spent, received) = measure_swap(
+                position.balance,
+                nav::token_balance(position.token_account)?,
+                output_before,
+                ctx.accounts.vault_base_token.amount,
+                route.amount_in,
+                route.min_amount_out,
+            )?;
+            
+            // Check the fill against the pair's oracle price, within the
+            // strategy's liquidation bound and the pair's guard if tighter
+            let spent_value = strategy.pair_value(
+                vault,
+                &position.pair,
+                spent,
+                Some(position.price_source),
+                position.base_price_source,
+                now,
+            )?;
+            let max_deviation_bps = match position.pair.max_oracle_deviation_bps {
+                0 => strategy.max_liquidation_deviation_bps,
+                pair_bps => pair_bps.min(strategy.max_liquidation_deviation_bps),
+            };
+            check_oracle_deviation(spent_value, received, max_deviation_bps)?;
+            
+            // The redeemer bears the sale's loss against NAV
+            slippage = slippage.saturating_add(spent_value.saturating_sub(received));
+            
+            emit!(PositionLiquidated {
+                token_mint: position.pair.token_mint,
+                amount: spent,
+                result: received,
+            });
+        }
         
-        // First burn shares
-        let cpi_accounts = token::Burn {
-            mint: ctx.accounts.vault_shares.to_account_info(),
-            from: ctx.accounts.user_shares.to_account_info(),
-            authority: ctx.accounts.authority.to_account_info(),
-        };
-        
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(
-            cpi_program,
-            cpi_accounts,
+        require!(
+            routes.next().is_none() && route_accounts.is_empty(),
+            MyVaultError::LiquidationRouteMismatch
         );
         
-        token::burn(cpi_ctx, shares_to_burn)?;
-        
-        // Transfer assets to user
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.vault_base_token.to_account_info(),
-            to: ctx.accounts.user_token.to_account_info(),
-            authority: ctx.accounts.vault_authority.to_account_info(),
-        };
-        
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_signer_seeds = &[&vault_authority_seeds[..]];
+        let assets = assets
+            .checked_sub(slippage)
+            .ok_or(MyVaultError::LiquidationShortfall)?
+            .saturating_sub(early_exit_fee);
+        require!(
+            assets >= min_assets_out,
+            MyVaultError::MinAssetsOutNotMet
+        );
         
-        let cpi_ctx = CpiContext::new_with_signer(
-            cpi_program,
-            cpi_accounts,
-            cpi_signer_seeds,
+        // Ensure vault has enough base assets
+        require!(
+            assets <= ctx.accounts.vault.available_base(ctx.accounts.vault_base_token.amount),
+            MyVaultError::InsufficientVaultBalance
         );
         
-        token::transfer(cpi_ctx, amount)?;
+        ctx.accounts.check_remaining_balance(shares, total_assets)?;
+        
+        ctx.accounts.burn_and_pay(shares, assets, ctx.bumps.vault_authority)?;
         
         emit!(Withdrawn {
             user: ctx.accounts.authority.key(),
-            amount,
-            shares: shares_to_burn,
+            amount: assets,
+            shares,
         });
         
         Ok(())
     }
     
-    // Update investor
-    pub fn update_investor(
-        ctx: Context<UpdateInvestor>,
-        new_investor: Pubkey,
+    // Request a withdrawal settled at the NAV of the end of the open epoch.
+    // The shares are escrowed until process_withdrawals settles the epoch,
+    // and can be taken back with cancel_withdrawal before then. Remaining
+    // accounts are the NAV accounts, to check the minimum balance.
+    pub fn request_withdraw(
+        ctx: Context<RequestWithdraw>,
+        shares: u64,
     ) -> Result<()> {
-        let vault = &mut ctx.accounts.vault;
+        ctx.accounts.position.collect_fee_shares(ctx.accounts.user_shares.amount);
         
-        // Check if it's vault admin
+        require!(shares > 0, MyVaultError::InvalidWithdrawAmount);
+        
+        // Ensure the position has enough shares
         require!(
-            vault.authority == ctx.accounts.authority.key(),
-            MyVaultError::Unauthorized
+            shares <= ctx.accounts.position.shares,
+            MyVaultError::InsufficientShares
         );
         
-        // Update investor
-        vault.investor = new_investor;
+        // Queued withdrawals are not available during the lock-up
+        require!(
+            Clock::get()?.unix_timestamp as u64 >= ctx.accounts.position.unlock_time,
+            MyVaultError::LockupActive
+        );
         
-        msg!("Investor updated to: {}", new_investor);
-        Ok(())
-    }
-}
-
-// Trading signal type
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
-pub enum SignalType {
-    Buy,
-    Sell,
-}
-
-// Trading pair definition
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+        // The position left must keep the minimum balance at the current NAV
+        let total_assets = ctx.accounts.total_assets(ctx.remaining_accounts)?;
+        ctx.accounts.check_remaining_balance(shares, total_assets)?;
+        
+        let vault = &mut ctx.accounts.vault;
+        
+        // Escrow shares
+        let vault_authority_seeds = &[
+            VAULT_SEED,
+            vault.base_token_mint.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        let user_shares = ctx.accounts.user_shares.to_account_info();
+        let vault_shares = ctx.accounts.vault_shares.to_account_info();
+        let vault_authority = ctx.accounts.vault_authority.to_account_info();
+        let token_program = ctx.accounts.token_program.to_account_info();
+        thaw_shares(&user_shares, &vault_shares, &vault_authority, &token_program, vault_authority_seeds)?;
+        
+        let cpi_accounts = Transfer {
+            from: user_shares.clone(),
+            to: ctx.accounts.withdraw_escrow.to_account_info(),
+            authority: ctx.accounts.authority.to_account_info(),
+        };
+        
+        let cpi_program = token_program.clone();
+        let cpi_ctx = CpiContext::new(
+            cpi_program,
+            cpi_accounts,
+        );
+        
+        token::transfer(cpi_ctx, shares)?;
+        freeze_shares(&user_shares, &vault_shares, &vault_authority, &token_program, vault_authority_seeds)?;
+        
+        vault.pending_withdraw_shares = vault.pending_withdraw_shares
+            .checked_add(shares)
+            .ok_or(MyVaultError::MathOverflow)?;
+        
+        let cost_basis = ctx.accounts.position.remove_shares(shares)?;
+        
+        let request = &mut ctx.accounts.withdraw_request;
+        request.vault = vault.key();
+        request.owner = ctx.accounts.authority.key();
+        request.epoch = vault.withdraw_epoch;
+        request.shares = shares;
+        request.cost_basis = cost_basis;
+        request.bump = ctx.bumps.withdraw_request;
+        
+        emit!(WithdrawRequested {
+            user: request.owner,
+            epoch: request.epoch,
+            shares,
+        });
+        
+        Ok(())
+    }
+    
+    // Settle the open epoch's withdrawal requests at NAV, burning their
+    // escrowed shares and reserving the base tokens to pay them. Called by a
+    // keeper once the strategy has freed enough base liquidity; remaining
+    // accounts are the NAV accounts.
+    pub fn process_withdrawals(ctx: Context<ProcessWithdrawals>) -> Result<()> {
+        // Check if it's an oracle or the vault admin
+        let keeper = ctx.accounts.keeper.key();
+        require!(
+            ctx.accounts.strategy.oracles.contains(&keeper) || ctx.accounts.vault.authority == keeper,
+            MyVaultError::Unauthorized
+        );
+        
+        let shares = ctx.accounts.vault.pending_withdraw_shares;
+        require!(shares > 0, MyVaultError::NoPendingWithdrawals);
+        
+        // Calculate assets to pay at NAV, same as redeem
+        let total_assets = ctx.accounts.total_assets(ctx.remaining_accounts)?;
+        ctx.accounts.accrue_fees(total_assets, ctx.bumps.vault_authority)?;
+        let assets = nav::convert_to_assets(shares, total_assets, ctx.accounts.vault_shares.supply, Rounding::Down)?;
+        
+        // Ensure vault has enough base assets
+        require!(
+            assets <= ctx.accounts.vault.available_base(ctx.accounts.vault_base_token.amount),
+            MyVaultError::InsufficientVaultBalance
+        );
+        
+        // Burn escrowed shares
+        let vault_authority_seeds = &[
+            VAULT_SEED,
+            ctx.accounts.vault.base_token_mint.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        
+        let cpi_accounts = token::Burn {
+            mint: ctx.accounts.vault_shares.to_account_info(),
+            from: ctx.accounts.withdraw_escrow.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_signer_seeds = &[&vault_authority_seeds[..]];
+        
+        let cpi_ctx = CpiContext::new_with_signer(
+            cpi_program,
+            cpi_accounts,
+            cpi_signer_seeds,
+        );
+        
+        token::burn(cpi_ctx, shares)?;
+        
+        let vault = &mut ctx.accounts.vault;
+        let withdraw_epoch = &mut ctx.accounts.withdraw_epoch;
+        withdraw_epoch.vault = vault.key();
+        withdraw_epoch.epoch = vault.withdraw_epoch;
+        withdraw_epoch.shares = shares;
+        withdraw_epoch.assets = assets;
+        withdraw_epoch.processed_at = Clock::get()?.unix_timestamp as u64;
+        withdraw_epoch.bump = ctx.bumps.withdraw_epoch;
+        
+        vault.reserved_assets = vault.reserved_assets
+            .checked_add(assets)
+            .ok_or(MyVaultError::MathOverflow)?;
+        vault.pending_withdraw_shares = 0;
+        vault.withdraw_epoch = vault.withdraw_epoch
+            .checked_add(1)
+            .ok_or(MyVaultError::MathOverflow)?;
+        
+        emit!(WithdrawalsProcessed {
+            epoch: withdraw_epoch.epoch,
+            shares,
+            assets,
+        });
+        
+        Ok(())
+    }
+    
+    // Claim the assets of a processed withdrawal request
+    pub fn claim_withdrawal(ctx: Context<ClaimWithdrawal>) -> Result<()> {
+        let request = &ctx.accounts.withdraw_request;
+        require!(
+            request.epoch < ctx.accounts.vault.withdraw_epoch,
+            MyVaultError::WithdrawRequestNotProcessed
+        );
+        
+        let assets = ctx.accounts.withdraw_epoch.claim(request.shares)?;
+        
+        let vault = &mut ctx.accounts.vault;
+        vault.reserved_assets = vault.reserved_assets.saturating_sub(assets);
+        
+        // Transfer assets to user
+        let vault_authority_seeds = &[
+            VAULT_SEED,
+            vault.base_token_mint.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.vault_base_token.to_account_info(),
+            to: ctx.accounts.user_token.to_account_info(),
+            authority: ctx.accounts.vault_authority.to_account_info(),
+        };
+        
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_signer_seeds = &[&vault_authority_seeds[..]];
+        
+        let cpi_ctx = CpiContext::new_with_signer(
+            cpi_program,
+            cpi_accounts,
+            cpi_signer_seeds,
+        );
+        
+        token::transfer(cpi_ctx, assets)?;
+        
+        ctx.accounts.position.record_withdraw(assets)?;
+        
+        emit!(Withdrawn {
+            user: ctx.accounts.authority.key(),
+            amount: assets,
+            shares: request.shares,
+        });
+        
+        Ok(())
+    }
+    
+    // Cancel a withdrawal request that has not been processed, returning
+    // its escrowed shares
+    pub fn cancel_withdrawal(ctx: Context<CancelWithdrawal>) -> Result<()> {
+        let request = &ctx.accounts.withdraw_request;
+        require!(
+            request.epoch == ctx.accounts.vault.withdraw_epoch,
+            MyVaultError::WithdrawRequestProcessed
+        );
+        
+        // Return escrowed shares
+        let vault_authority_seeds = &[
+            VAULT_SEED,
+            ctx.accounts.vault.base_token_mint.as_ref(),
+            &[ctx.bumps.vault_authority],
+        ];
+        
+        let user_shares = ctx.accounts.user_shares.to_account_info();
+        let vault_shares = ctx.accounts.vault_shares.to_account_info();
+        let vault_authority = ctx.accounts.vault_authority.to_account_info();
+        let token_program = ctx.accounts.token_program.to_account_info();
+        thaw_shares(&user_shares, &vault_shares, &vault_authority, &token_program, vault_authority_seeds)?;
+        
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.withdraw_escrow.to_account_info(),
+            to: user_shares.clone(),
+            authority: vault_authority.clone(),
+        };
+        
+        let cpi_program = token_program.clone();
+        let cpi_signer_seeds = &[&vault_authority_seeds[..]];
+        
+        let cpi_ctx = CpiContext::new_with_signer(
+            cpi_program,
+            cpi_accounts,
+            cpi_signer_seeds,
+        );
+        
+        token::transfer(cpi_ctx, request.shares)?;
+        freeze_shares(&user_shares, &vault_shares, &vault_authority, &token_program, vault_authority_seeds)?;
+        
+        let vault = &mut ctx.accounts.vault;
+        vault.pending_withdraw_shares = vault.pending_withdraw_shares
+            .checked_sub(request.shares)
+            .ok_or(MyVaultError::MathOverflow)?;
+        
+        ctx.accounts.position.restore_shares(request.shares, request.cost_basis)?;
+        
+        msg!("Withdrawal request cancelled: {} shares", request.shares);
+        Ok(())
+    }
+    
+    // ERC4626-style views. Values are returned through set_return_data so
+    // clients can simulate them; remaining accounts are the NAV accounts.
+    
+    // Vault NAV in base token units
+    pub fn total_assets(ctx: Context<VaultView>) -> Result<u64> {
+        ctx.accounts.total_assets(ctx.remaining_accounts)
+    }
+    
+    // Shares minted by depositing assets
+    pub fn preview_deposit(ctx: Context<VaultView>, assets: u64) -> Result<u64> {
+        let total_assets = ctx.accounts.total_assets(ctx.remaining_accounts)?;
+        nav::convert_to_shares(assets, total_assets, ctx.accounts.total_shares(total_assets)?, Rounding::Down)
+    }
+    
+    // Assets needed to mint shares
+    pub fn preview_mint(ctx: Context<VaultView>, shares: u64) -> Result<u64> {
+        let total_assets = ctx.accounts.total_assets(ctx.remaining_accounts)?;
+        nav::convert_to_assets(shares, total_assets, ctx.accounts.total_shares(total_assets)?, Rounding::Up)
+    }
+    
+    // Shares burned by withdrawing assets
+    pub fn preview_withdraw(ctx: Context<VaultView>, assets: u64) -> Result<u64> {
+        let total_assets = ctx.accounts.total_assets(ctx.remaining_accounts)?;
+        nav::convert_to_shares(assets, total_assets, ctx.accounts.total_shares(total_assets)?, Rounding::Up)
+    }
+    
+    // Assets received by redeeming shares
+    pub fn preview_redeem(ctx: Context<VaultView>, shares: u64) -> Result<u64> {
+        let total_assets = ctx.accounts.total_assets(ctx.remaining_accounts)?;
+        nav::convert_to_assets(shares, total_assets, ctx.accounts.total_shares(total_assets)?, Rounding::Down)
+    }
+    
+    // Assets the owner of a position can withdraw now, net of any early exit
+    // fee, see max_redeem
+    pub fn max_withdraw(ctx: Context<OwnerView>) -> Result<u64> {
+        let total_assets = ctx.accounts.total_assets(ctx.remaining_accounts)?;
+        let total_shares = ctx.accounts.total_shares(total_assets)?;
+        let shares = ctx.accounts.max_redeem(total_assets, total_shares)?;
+        if shares == 0 {
+            return Ok(0);
+        }
+        let assets = nav::convert_to_assets(shares, total_assets, total_shares, Rounding::Down)?;
+        ctx.accounts.vault.assets_after_early_exit_fee(&ctx.accounts.position, assets, Clock::get()?.unix_timestamp as u64)
+    }
+    
+    // Shares the owner of a position can redeem now: limited by the lock-up,
+    // the vault base balance and the minimum balance
+    pub fn max_redeem(ctx: Context<OwnerView>) -> Result<u64> {
+        let total_assets = ctx.accounts.total_assets(ctx.remaining_accounts)?;
+        let total_shares = ctx.accounts.total_shares(total_assets)?;
+        ctx.accounts.max_redeem(total_assets, total_shares)
+    }
+    
+    // Crystallize management and performance fees accrued since the last
+    // accrual, minting fee shares to the fee recipient. Anyone may call it;
+    // remaining accounts are the NAV accounts.
+    pub fn accrue_fees(ctx: Context<AccrueFees>) -> Result<()> {
+        let total_assets = ctx.accounts.total_assets(ctx.remaining_accounts)?;
+        ctx.accounts.accrue_fees(total_assets, ctx.bumps.vault_authority)
+    }
+    
+    // Set vault fees, accruing fees at the previous rates first
+    pub fn set_fees(
+        ctx: Context<AccrueFees>,
+        management_fee_bps: u16,
+        performance_fee_bps: u16,
+        fee_recipient: Pubkey,
+    ) -> Result<()> {
+        // Check if it's vault admin
+        require!(
+            ctx.accounts.vault.authority == ctx.accounts.authority.key(),
+            MyVaultError::Unauthorized
+        );
+        
+        require!(
+            management_fee_bps <= MAX_MANAGEMENT_FEE_BPS && performance_fee_bps <= MAX_PERFORMANCE_FEE_BPS,
+            MyVaultError::InvalidFee
+        );
+        
+        let total_assets = ctx.accounts.total_assets(ctx.remaining_accounts)?;
+        ctx.accounts.accrue_fees(total_assets, ctx.bumps.vault_authority)?;
+        
+        let vault = &mut ctx.accounts.vault;
+        // The new rates only apply from now on, even to a fee too small to mint
+        vault.last_fee_accrual = Clock::get()?.unix_timestamp as u64;
+        vault.management_fee_bps = management_fee_bps;
+        vault.performance_fee_bps = performance_fee_bps;
+        vault.fee_recipient = fee_recipient;
+        
+        msg!(
+            "Fees updated: management {} bps, performance {} bps, recipient {}",
+            management_fee_bps,
+            performance_fee_bps,
+            fee_recipient
+        );
+        Ok(())
+    }
+    
+    // Update investor
+    pub fn update_investor(
+        ctx: Context<UpdateInvestor>,
+        new_investor: Pubkey,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        
+        // Check if it's vault admin
+        require!(
+            vault.authority == ctx.accounts.authority.key(),
+            MyVaultError::Unauthorized
+        );
+        
+        // Update investor
+        vault.investor = new_investor;
+        
+        msg!("Investor updated to: {}", new_investor);
+        Ok(())
+    }
+    
+    // Set deposit caps and minimums, zero caps are unlimited
+    pub fn set_deposit_limits(
+        ctx: Context<UpdateInvestor>,
+        tvl_cap: u64,
+        investor_cap: u64,
+        min_deposit: u64,
+        min_balance: u64,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        
+        // Check if it's vault admin
+        require!(
+            vault.authority == ctx.accounts.authority.key(),
+            MyVaultError::Unauthorized
+        );
+        
+        vault.tvl_cap = tvl_cap;
+        vault.investor_cap = investor_cap;
+        vault.min_deposit = min_deposit;
+        vault.min_balance = min_balance;
+        
+        emit!(DepositLimitsUpdated {
+            vault: vault.key(),
+            tvl_cap,
+            investor_cap,
+            min_deposit,
+            min_balance,
+        });
+        
+        Ok(())
+    }
+    
+    // Set the lock-up started by each deposit and the fee on withdrawals
+    // during it, zero refuses them
+    pub fn set_lockup(
+        ctx: Context<UpdateInvestor>,
+        lockup_period: u64,
+        early_exit_fee_bps: u16,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        
+        // Check if it's vault admin
+        require!(
+            vault.authority == ctx.accounts.authority.key(),
+            MyVaultError::Unauthorized
+        );
+        
+        require!(
+            early_exit_fee_bps <= MAX_EARLY_EXIT_FEE_BPS,
+            MyVaultError::InvalidFee
+        );
+        
+        vault.lockup_period = lockup_period;
+        vault.early_exit_fee_bps = early_exit_fee_bps;
+        
+        msg!("Lock-up updated: {} seconds, early exit fee {} bps", lockup_period, early_exit_fee_bps);
+        Ok(())
+    }
+    
+    // Set who may deposit into the vault
+    pub fn set_investor_access(
+        ctx: Context<UpdateInvestor>,
+        investor_access: InvestorAccess,
+    ) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        
+        // Check if it's vault admin
+        require!(
+            vault.authority == ctx.accounts.authority.key(),
+            MyVaultError::Unauthorized
+        );
+        
+        vault.investor_access = investor_access;
+        
+        msg!("Investor access updated to: {:?}", investor_access);
+        Ok(())
+    }
+    
+    // Allow an investor to deposit into an allowlisted vault, creating their
+    // position if needed
+    pub fn add_allowed_investor(
+        ctx: Context<AddAllowedInvestor>,
+        investor: Pubkey,
+    ) -> Result<()> {
+        // Check if it's vault admin
+        require!(
+            ctx.accounts.vault.authority == ctx.accounts.authority.key(),
+            MyVaultError::Unauthorized
+        );
+        
+        let position = &mut ctx.accounts.position;
+        position.vault = ctx.accounts.vault.key();
+        position.owner = investor;
+        position.allowlisted = true;
+        position.bump = ctx.bumps.position;
+        
+        msg!("Investor allowlisted: {}", investor);
+        Ok(())
+    }
+    
+    // Remove an investor from the allowlist. They keep their shares and can
+    // still withdraw.
+    pub fn remove_allowed_investor(
+        ctx: Context<RemoveAllowedInvestor>,
+        investor: Pubkey,
+    ) -> Result<()> {
+        // Check if it's vault admin
+        require!(
+            ctx.accounts.vault.authority == ctx.accounts.authority.key(),
+            MyVaultError::Unauthorized
+        );
+        
+        ctx.accounts.position.allowlisted = false;
+        
+        msg!("Investor removed from allowlist: {}", investor);
+        Ok(())
+    }
+    
+    // Create the caller's position, needed before depositing
+    pub fn open_position(ctx: Context<OpenPosition>) -> Result<()> {
+        let position = &mut ctx.accounts.position;
+        position.vault = ctx.accounts.vault.key();
+        position.owner = ctx.accounts.authority.key();
+        position.allowlisted = false;
+        position.deposited = 0;
+        position.withdrawn = 0;
+        position.shares = 0;
+        position.cost_basis = 0;
+        position.unlock_time = 0;
+        position.bump = ctx.bumps.position;
+        
+        Ok(())
+    }
+}
+
+// Crystallize the fees accrued since the vault's last accrual by minting
+// fee shares to the fee recipient. Runs before shares are minted or burned
+// at NAV, so every deposit and withdraw prices shares net of fees.
+fn accrue_vault_fees<'info>(
+    vault: &mut Account<'info, Vault>,
+    vault_shares: &mut Account<'info, Mint>,
+    fee_recipient_shares: &Account<'info, TokenAccount>,
+    vault_authority: &AccountInfo<'info>,
+    token_program: &Program<'info, Token>,
+    total_assets: u64,
+    vault_authority_bump: u8,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp as u64;
+    let accrual = fees::accrue(
+        total_assets,
+        vault_shares.supply,
+        vault.management_fee_bps,
+        vault.performance_fee_bps,
+        vault.high_water_mark,
+        now.saturating_sub(vault.last_fee_accrual),
+    )?;
+    vault.high_water_mark = accrual.high_water_mark;
+    if accrual.settles_management_fee(vault_shares.supply, vault.management_fee_bps) {
+        vault.last_fee_accrual = now;
+    }
+    
+    if accrual.shares > 0 {
+        let vault_authority_seeds = &[
+            VAULT_SEED,
+            vault.base_token_mint.as_ref(),
+            &[vault_authority_bump],
+        ];
+        
+        let recipient_info = fee_recipient_shares.to_account_info();
+        let shares_info = vault_shares.to_account_info();
+        let token_program_info = token_program.to_account_info();
+        thaw_shares(&recipient_info, &shares_info, vault_authority, &token_program_info, vault_authority_seeds)?;
+        
+        let cpi_accounts = token::MintTo {
+            mint: vault_shares.to_account_info(),
+            to: fee_recipient_shares.to_account_info(),
+            authority: vault_authority.to_account_info(),
+        };
+        
+        let cpi_program = token_program.to_account_info();
+        let cpi_signer_seeds = &[&vault_authority_seeds[..]];
+        
+        let cpi_ctx = CpiContext::new_with_signer(
+            cpi_program,
+            cpi_accounts,
+            cpi_signer_seeds,
+        );
+        
+        token::mint_to(cpi_ctx, accrual.shares)?;
+        freeze_shares(&recipient_info, &shares_info, vault_authority, &token_program_info, vault_authority_seeds)?;
+        vault_shares.reload()?;
+    }
+    
+    emit!(FeesAccrued {
+        fee_recipient: vault.fee_recipient,
+        management_fee: accrual.management_fee,
+        performance_fee: accrual.performance_fee,
+        shares: accrual.shares,
+        high_water_mark: accrual.high_water_mark,
+        timestamp: now,
+    });
+    
+    Ok(())
+}
+
+// Vault shares are not transferable: share token accounts are kept frozen
+// and only thawed while the vault mints, burns or moves their shares, so a
+// position always matches the shares its owner holds. Thaw a share token
+// account if it is frozen.
+fn thaw_shares<'info>(
+    shares_account: &AccountInfo<'info>,
+    vault_shares: &AccountInfo<'info>,
+    vault_authority: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    vault_authority_seeds: &[&[u8]],
+) -> Result<()> {
+    // Read the account as it is now, it may have been frozen earlier in the
+    // same instruction
+    let account = TokenAccount::try_deserialize(&mut &shares_account.try_borrow_data()?[..])?;
+    if !account.is_frozen() {
+        return Ok(());
+    }
+    
+    let cpi_accounts = token::ThawAccount {
+        account: shares_account.clone(),
+        mint: vault_shares.clone(),
+        authority: vault_authority.clone(),
+    };
+    let cpi_signer_seeds = &[vault_authority_seeds];
+    token::thaw_account(CpiContext::new_with_signer(token_program.clone(), cpi_accounts, cpi_signer_seeds))
+}
+
+// Freeze a share token account again once the vault is done with it
+fn freeze_shares<'info>(
+    shares_account: &AccountInfo<'info>,
+    vault_shares: &AccountInfo<'info>,
+    vault_authority: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    vault_authority_seeds: &[&[u8]],
+) -> Result<()> {
+    let cpi_accounts = token::FreezeAccount {
+        account: shares_account.clone(),
+        mint: vault_shares.clone(),
+        authority: vault_authority.clone(),
+    };
+    let cpi_signer_seeds = &[vault_authority_seeds];
+    token::freeze_account(CpiContext::new_with_signer(token_program.clone(), cpi_accounts, cpi_signer_seeds))
+}
+
+// The vault holds the base token in its base token account and every other
+// token in the vault authority's associated token account
+fn check_vault_token_account(
+    vault: &Vault,
+    vault_authority: &Pubkey,
+    mint: &Pubkey,
+    token_account: &Pubkey,
+) -> Result<()> {
+    let expected = if *mint == vault.base_token_mint {
+        vault.base_token_account
+    } else {
+        get_associated_token_address(vault_authority, mint)
+    };
+    require_keys_eq!(*token_account, expected, MyVaultError::InvalidVaultTokenAccount);
+    Ok(())
+}
+
+// Value of a token amount in base token units at a PRICE_SCALE price, rounded down
+fn value_in_base(amount: u64, price: u128) -> Result<u64> {
+    math::to_u64(math::mul_div_floor(amount as u128, price, PRICE_SCALE)?)
+}
+
+// A trade may not lose more than max_deviation_bps of the value spent,
+// with both sides valued in base token units at the oracle price
+fn check_oracle_deviation(spent_value: u64, received_value: u64, max_deviation_bps: u16) -> Result<()> {
+    let min_received_value = math::bps(spent_value, BASIS_POINTS - max_deviation_bps as u64, Rounding::Up)?;
+    require!(
+        received_value >= min_received_value,
+        MyVaultError::OracleDeviationExceeded
+    );
+    Ok(())
+}
+
+// Amounts a swap spent and received, measured from vault balances before and
+// after the CPI. Rejects swaps that spent more than max_amount_in, received
+// less than min_amount_out or took tokens out of the output account.
+fn measure_swap(
+    input_before: u64,
+    input_after: u64,
+    output_before: u64,
+    output_after: u64,
+    max_amount_in: u64,
+    min_amount_out: u64,
+) -> Result<(u64, u64)> {
+    let amount_in = input_before.saturating_sub(input_after);
+    let amount_out = output_after
+        .checked_sub(output_before)
+        .ok_or(MyVaultError::SlippageExceeded)?;
+    
+    require!(
+        amount_in <= max_amount_in,
+        MyVaultError::SwapInputExceedsAmount
+    );
+    require!(
+        amount_out >= min_amount_out,
+        MyVaultError::SlippageExceeded
+    );
+    Ok((amount_in, amount_out))
+}
+
+// Route instruction with the vault authority PDA marked as a signer
+fn swap_route_instruction(
+    swap_program: Pubkey,
+    vault_authority: Pubkey,
+    route_accounts: &[AccountInfo],
+    route_data: Vec<u8>,
+) -> anchor_lang::solana_program::instruction::Instruction {
+    anchor_lang::solana_program::instruction::Instruction {
+        program_id: swap_program,
+        accounts: route_accounts.iter().map(|a| {
+            anchor_lang::solana_program::instruction::AccountMeta {
+                pubkey: a.key(),
+                is_signer: a.is_signer || a.key() == vault_authority,
+                is_writable: a.is_writable,
+            }
+        }).collect(),
+        data: route_data,
+    }
+}
+
+// Invoke a swap route with the vault authority PDA signing as the user
+fn invoke_swap_route(
+    swap_program: Pubkey,
+    vault_authority: Pubkey,
+    route_accounts: &[AccountInfo],
+    route_data: Vec<u8>,
+    vault_authority_seeds: &[&[u8]],
+) -> Result<()> {
+    let route_instruction = swap_route_instruction(swap_program, vault_authority, route_accounts, route_data);
+    
+    anchor_lang::solana_program::program::invoke_signed(
+        &route_instruction,
+        route_accounts,
+        &[vault_authority_seeds],
+    )?;
+    
+    Ok(())
+}
+
+// Trading signal type
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SignalType {
+    Buy,
+    Sell,
+}
+
+// Swap venue a trading pair is traded through
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SwapVenue {
+    Jupiter,
+    OrcaWhirlpool,
+    RaydiumClmm,
+    ConstantProduct,
+}
+
+// Allowlisted swap program and the venue its instructions are decoded as
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SwapProgram {
+    pub program_id: Pubkey,
+    pub venue: SwapVenue,
+}
+
+// Price source used to value a trading pair
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PriceSource {
+    PriceFeed,                     // The pair's PriceFeed account
+    Pyth { feed_id: [u8; 32] },    // Pyth PriceUpdateV2 account of the feed
+}
+
+// Trading pair definition
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
 pub struct TradingPair {
     pub token_mint: Pubkey,
     pub is_active: bool,
     pub max_allocation: u64,
     pub min_exit_amount: u64,
+    pub venue: SwapVenue,
+    pub price_source: PriceSource,
+    pub token_decimals: u8,
+    pub max_oracle_deviation_bps: u16,
+}
+
+impl TradingPair {
+    // Sells that would leave less than min_exit_amount become full exits, and
+    // sells below min_exit_amount are only allowed as full exits
+    pub fn exit_amount(&self, amount: u64, balance: u64) -> Result<u64> {
+        let remaining = balance
+            .checked_sub(amount)
+            .ok_or(MyVaultError::InsufficientVaultBalance)?;
+        let amount = if remaining < self.min_exit_amount {
+            balance
+        } else {
+            amount
+        };
+        require!(
+            amount >= self.min_exit_amount || amount == balance,
+            MyVaultError::SellBelowMinExit
+        );
+        Ok(amount)
+    }
+}
+
+// Signed trading signal, borsh-serialized as the Ed25519 message.
+// The signal expires signal_timeout seconds after issued_at.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct SignalMessage {
+    pub vault: Pubkey,
+    pub token_mint: Pubkey,
+    pub signal_type: SignalType,
+    pub amount: u64,
+    pub min_amount_out: u64,
+    pub issued_at: u64,
+    pub nonce: u64,
+}
+
+// Signed swap between two vault tokens, borsh-serialized as the Ed25519
+// message. Expires like SignalMessage.
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct SwapSignalMessage {
+    pub vault: Pubkey,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub amount: u64,
+    pub min_amount_out: u64,
+    pub issued_at: u64,
+    pub nonce: u64,
+}
+
+// Swap route selling one position in redeem_with_liquidation. The route's
+// accounts are the swap program followed by accounts_len venue accounts.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LiquidationRoute {
+    pub token_mint: Pubkey,
+    pub amount_in: u64,
+    pub min_amount_out: u64,
+    pub accounts_len: u8,
+    pub data: Vec<u8>,
+}
+
+// Event definition
+#[event]
+pub struct SignalReceived {
+    pub signal_type: SignalType,
+    pub token_mint: Pubkey,
+    pub timestamp: u64,
+    pub issued_at: u64,
+    pub signer: Pubkey,
+    pub nonce: u64,
+}
+
+#[event]
+pub struct TradeExecuted {
+    pub signal_type: SignalType,
+    pub token_mint: Pubkey,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub amount: u64,
+    pub result: u64,
+    pub nonce: u64,
+}
+
+#[event]
+pub struct PriceUpdated {
+    pub token_mint: Pubkey,
+    pub base_mint: Pubkey,
+    pub price: u128,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct PositionLiquidated {
+    pub token_mint: Pubkey,
+    pub amount: u64,
+    pub result: u64,
+}
+
+#[event]
+pub struct DepositLimitsUpdated {
+    pub vault: Pubkey,
+    pub tvl_cap: u64,
+    pub investor_cap: u64,
+    pub min_deposit: u64,
+    pub min_balance: u64,
+}
+
+#[event]
+pub struct WithdrawRequested {
+    pub user: Pubkey,
+    pub epoch: u64,
+    pub shares: u64,
+}
+
+#[event]
+pub struct WithdrawalsProcessed {
+    pub epoch: u64,
+    pub shares: u64,
+    pub assets: u64,
+}
+
+#[event]
+pub struct FeesAccrued {
+    pub fee_recipient: Pubkey,
+    pub management_fee: u64,
+    pub performance_fee: u64,
+    pub shares: u64,
+    pub high_water_mark: u128,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct Deposited {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+}
+
+#[event]
+pub struct Withdrawn {
+    pub user: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+}
+
+// Error code
+#[error_code]
+pub enum MyVaultError {
+    #[msg("Unauthorized operation")]
+    Unauthorized,
+    
+    #[msg("Strategy not enabled")]
+    StrategyNotEnabled,
+    
+    #[msg("Trading pair not active")]
+    TradingPairNotActive,
+    
+    #[msg("Allocation exceeds maximum")]
+    AllocationExceedsMaximum,
+    
+    #[msg("Swap amount exceeds allocation")]
+    SwapAmountExceedsAllocation,
+    
+    #[msg("Invalid allocation percentage")]
+    InvalidAllocation,
+    
+    #[msg("Invalid percentage value")]
+    InvalidPercentage,
+    
+    #[msg("Insufficient shares")]
+    InsufficientShares,
+    
+    #[msg("Insufficient vault balance")]
+    InsufficientVaultBalance,
+    
+    #[msg("Only specified investor can operate")]
+    OnlyInvestorAllowed,
+    
+    #[msg("Account not found")]
+    AccountNotFound,
+    
+    #[msg("Cannot withdraw 0 amount of assets")]
+    InvalidWithdrawAmount,
+    
+    #[msg("Signer is not a registered oracle")]
+    OracleNotRegistered,
+    
+    #[msg("Oracle already registered")]
+    OracleAlreadyRegistered,
+    
+    #[msg("Oracle registry is full")]
+    OracleRegistryFull,
+    
+    #[msg("Signal expired")]
+    SignalExpired,
+    
+    #[msg("Signal timestamp is too far in the future")]
+    InvalidSignalTimestamp,
+    
+    #[msg("Signed signal requires a preceding Ed25519 instruction")]
+    MissingSignatureInstruction,
+    
+    #[msg("Ed25519 instruction does not match signal signer or payload")]
+    InvalidSignalSignature,
+    
+    #[msg("Signal nonce already used")]
+    SignalReplayed,
+    
+    #[msg("Signal nonce is too far behind the latest nonce")]
+    SignalNonceTooOld,
+    
+    #[msg("Swap output below minimum amount out")]
+    SlippageExceeded,
+    
+    #[msg("Swap spent more than the signal amount")]
+    SwapInputExceedsAmount,
+    
+    #[msg("Swap program not allowlisted")]
+    SwapProgramNotAllowed,
+    
+    #[msg("Swap program already allowlisted")]
+    SwapProgramAlreadyAllowed,
+    
+    #[msg("Swap program allowlist is full")]
+    SwapProgramListFull,
+    
+    #[msg("Unsupported swap route instruction")]
+    UnsupportedRouteInstruction,
+    
+    #[msg("Swap route does not match the signal")]
+    RouteMismatch,
+    
+    #[msg("Swap input and output mints must differ")]
+    SameSwapMints,
+    
+    #[msg("Swap amount must be greater than zero")]
+    InvalidSwapAmount,
+    
+    #[msg("Token account is not the vault account for this mint")]
+    InvalidVaultTokenAccount,
+    
+    #[msg("Sell is below the minimum exit amount and not a full exit")]
+    SellBelowMinExit,
+    
+    #[msg("Price must be positive")]
+    InvalidPrice,
+    
+    #[msg("Price not available")]
+    PriceNotAvailable,
+    
+    #[msg("Price is stale")]
+    PriceStale,
+    
+    #[msg("Price feed does not belong to this vault")]
+    InvalidPriceFeed,
+    
+    #[msg("Price batch must be non-empty and match the price feed accounts")]
+    PriceBatchMismatch,
+    
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    
+    #[msg("Price source account required")]
+    MissingPriceSource,
+    
+    #[msg("Not a Pyth price update account for the expected feed")]
+    InvalidPythAccount,
+    
+    #[msg("Pyth price update is not fully verified")]
+    PythPriceUnverified,
+    
+    #[msg("Pyth price confidence interval too wide")]
+    PythConfidenceTooWide,
+    
+    #[msg("Trade price deviates too far from the oracle price")]
+    OracleDeviationExceeded,
+    
+    #[msg("NAV accounts do not match the strategy trading pairs")]
+    InvalidNavAccounts,
+    
+    #[msg("Deposit would mint zero shares")]
+    ZeroShares,
+    
+    #[msg("Division by zero")]
+    DivisionByZero,
+    
+    #[msg("Mint costs more than the maximum assets in")]
+    MaxAssetsInExceeded,
+    
+    #[msg("Redeem pays less than the minimum assets out")]
+    MinAssetsOutNotMet,
+    
+    #[msg("Liquidation routes do not match the positions to sell")]
+    LiquidationRouteMismatch,
+    
+    #[msg("Fee exceeds the maximum")]
+    InvalidFee,
+    
+    #[msg("No withdrawal requests to process")]
+    NoPendingWithdrawals,
+    
+    #[msg("Withdrawal request has not been processed yet")]
+    WithdrawRequestNotProcessed,
+    
+    #[msg("Withdrawal request has already been processed")]
+    WithdrawRequestProcessed,
+    
+    #[msg("Investor is not on the vault allowlist")]
+    InvestorNotAllowlisted,
+    
+    #[msg("Deposit is below the vault minimum")]
+    DepositBelowMinimum,
+    
+    #[msg("Deposit would exceed the vault TVL cap")]
+    TvlCapExceeded,
+    
+    #[msg("Deposit would exceed the investor cap")]
+    InvestorCapExceeded,
+    
+    #[msg("Withdrawal would leave less than the minimum balance")]
+    BelowMinimumBalance,
+    
+    #[msg("Shares are still locked up")]
+    LockupActive,
+    
+    #[msg("Swap program is allowlisted for another venue")]
+    SwapVenueMismatch,
+    
+    #[msg("Pyth feed already prices another token")]
+    PythFeedAlreadyBound,
+    
+    #[msg("Liquidation losses exceed the redeemed assets")]
+    LiquidationShortfall,
+}
+
+// Vault account structure
+#[account]
+#[derive(Default)]
+pub struct Vault {
+    pub base_token_mint: Pubkey,     // Base token mint
+    pub base_token_account: Pubkey,  // Base token account
+    pub authority: Pubkey,           // Admin
+    pub strategy: Pubkey,            // Strategy account
+    pub name: String,                // Vault name
+    pub investor: Pubkey,            // Investor
+    pub management_fee_bps: u16,     // Annual management fee on NAV
+    pub performance_fee_bps: u16,    // Performance fee on gains above the high-water mark
+    pub fee_recipient: Pubkey,       // Owner of minted fee shares
+    pub high_water_mark: u128,       // Highest share price fees were charged at, scaled by PRICE_SCALE
+    pub last_fee_accrual: u64,       // Last fee accrual timestamp
+    pub withdraw_epoch: u64,         // Epoch open for withdrawal requests
+    pub pending_withdraw_shares: u64, // Shares escrowed by requests in the open epoch
+    pub reserved_assets: u64,        // Base tokens owed to processed, unclaimed requests
+    pub investor_access: InvestorAccess, // Who may deposit
+    pub tvl_cap: u64,                // Maximum NAV after a deposit, zero for no cap
+    pub investor_cap: u64,           // Maximum investor holding after a deposit, zero for no cap
+    pub min_deposit: u64,            // Minimum deposit
+    pub min_balance: u64,            // Minimum holding left after a partial withdrawal
+    pub lockup_period: u64,          // Lock-up started by each deposit, in seconds
+    pub early_exit_fee_bps: u16,     // Fee on withdrawals during the lock-up, zero refuses them
+    pub bump: u8,                    // PDA bump
+}
+
+impl Vault {
+    // Base balance not reserved for processed withdrawal requests
+    pub fn available_base(&self, base_balance: u64) -> u64 {
+        base_balance.saturating_sub(self.reserved_assets)
+    }
+    
+    // Check an investor may deposit under the vault's investor access
+    pub fn check_deposit(&self, investor: &Pubkey, position: &InvestorPosition) -> Result<()> {
+        match self.investor_access {
+            InvestorAccess::Single => require!(self.investor == *investor, MyVaultError::OnlyInvestorAllowed),
+            InvestorAccess::Allowlisted => require!(position.allowlisted, MyVaultError::InvestorNotAllowlisted),
+            InvestorAccess::Open => {}
+        }
+        Ok(())
+    }
+    
+    // Check a deposit against the vault limits. Amounts are in base token
+    // units at NAV: `total_assets` before the deposit and `investor_assets`
+    // the investor's holding after it.
+    pub fn check_deposit_limits(&self, assets: u64, total_assets: u64, investor_assets: u64) -> Result<()> {
+        require!(assets >= self.min_deposit, MyVaultError::DepositBelowMinimum);
+        require!(
+            self.tvl_cap == 0 || total_assets.saturating_add(assets) <= self.tvl_cap,
+            MyVaultError::TvlCapExceeded
+        );
+        require!(
+            self.investor_cap == 0 || investor_assets <= self.investor_cap,
+            MyVaultError::InvestorCapExceeded
+        );
+        Ok(())
+    }
+    
+    // Check what an investor has left after a withdrawal, either nothing or
+    // at least the minimum balance
+    pub fn check_remaining_balance(&self, remaining_shares: u64, remaining_assets: u64) -> Result<()> {
+        require!(
+            remaining_shares == 0 || remaining_assets >= self.min_balance,
+            MyVaultError::BelowMinimumBalance
+        );
+        Ok(())
+    }
+    
+    // Early exit fee on withdrawing assets from a position at `now`. Refused
+    // during the lock-up when the vault charges no early exit fee.
+    pub fn early_exit_fee(&self, position: &InvestorPosition, assets: u64, now: u64) -> Result<u64> {
+        if now >= position.unlock_time {
+            return Ok(0);
+        }
+        require!(self.early_exit_fee_bps > 0, MyVaultError::LockupActive);
+        math::bps(assets, self.early_exit_fee_bps as u64, Rounding::Up)
+    }
+    
+    // Assets paid for withdrawing `assets` from a position at `now`, less
+    // the early exit fee
+    pub fn assets_after_early_exit_fee(&self, position: &InvestorPosition, assets: u64, now: u64) -> Result<u64> {
+        let fee = self.early_exit_fee(position, assets, now)?;
+        assets.checked_sub(fee).ok_or_else(|| error!(MyVaultError::MathOverflow))
+    }
+    
+    // Shares a position holding `shares` can redeem now. None during a
+    // lock-up without an early exit fee; otherwise all of them if the base
+    // balance covers them, or as many as it covers while leaving at least
+    // the minimum balance.
+    pub fn max_redeem(
+        &self,
+        position: &InvestorPosition,
+        shares: u64,
+        base_balance: u64,
+        total_assets: u64,
+        total_shares: u64,
+        now: u64,
+    ) -> Result<u64> {
+        if now < position.unlock_time && self.early_exit_fee_bps == 0 {
+            return Ok(0);
+        }
+        let liquid_shares = nav::convert_to_shares(self.available_base(base_balance), total_assets, total_shares, Rounding::Down)?;
+        if shares <= liquid_shares {
+            return Ok(shares);
+        }
+        let min_shares = nav::convert_to_shares(self.min_balance, total_assets, total_shares, Rounding::Up)?;
+        Ok(liquid_shares.min(shares.saturating_sub(min_shares)))
+    }
+}
+
+// Who may deposit into a vault
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum InvestorAccess {
+    #[default]
+    Single,       // Only vault.investor, the original single-investor vault
+    Allowlisted,  // Investors whose position the admin allowlisted
+    Open,         // Anyone with a position
+}
+
+// Per-investor position in a vault. It records the shares the investor
+// deposited for and has not withdrawn or queued, and their cost basis: the
+// base tokens paid for them, reduced pro rata when shares are withdrawn.
+#[account]
+pub struct InvestorPosition {
+    pub vault: Pubkey,       // Vault
+    pub owner: Pubkey,       // Investor
+    pub allowlisted: bool,   // Allowed to deposit into an allowlisted vault
+    pub deposited: u64,      // Total base tokens deposited
+    pub withdrawn: u64,      // Total base tokens withdrawn
+    pub shares: u64,         // Shares held
+    pub cost_basis: u64,     // Cost of the shares held
+    pub unlock_time: u64,    // End of the lock-up, averaged over deposits weighted by shares
+    pub bump: u8,            // PDA bump
+}
+
+impl InvestorPosition {
+    // Record a deposit of assets for shares. The deposit's lock-up is
+    // averaged with what is left of the current one.
+    fn record_deposit(&mut self, assets: u64, shares: u64, unlock_time: u64, now: u64) -> Result<()> {
+        self.deposited = self.deposited.checked_add(assets).ok_or(MyVaultError::MathOverflow)?;
+        self.cost_basis = self.cost_basis.checked_add(assets).ok_or(MyVaultError::MathOverflow)?;
+        
+        let locked = (self.unlock_time.max(now) as u128)
+            .checked_mul(self.shares as u128)
+            .and_then(|held| held.checked_add(unlock_time as u128 * shares as u128))
+            .ok_or(MyVaultError::MathOverflow)?;
+        self.unlock_time = math::to_u64(locked.div_ceil(self.shares as u128 + shares as u128))?;
+        self.shares = self.shares.checked_add(shares).ok_or(MyVaultError::MathOverflow)?;
+        Ok(())
+    }
+    
+    // Remove `shares` and their pro rata cost from the position, returning
+    // the cost
+    fn remove_shares(&mut self, shares: u64) -> Result<u64> {
+        let cost = if shares >= self.shares {
+            self.cost_basis
+        } else {
+            math::mul_div_u64(self.cost_basis, shares, self.shares, Rounding::Down)?
+        };
+        self.shares = self.shares.saturating_sub(shares);
+        self.cost_basis = self.cost_basis.checked_sub(cost).ok_or(MyVaultError::MathOverflow)?;
+        Ok(cost)
+    }
+    
+    // Return shares and their cost to the position
+    fn restore_shares(&mut self, shares: u64, cost: u64) -> Result<()> {
+        self.shares = self.shares.checked_add(shares).ok_or(MyVaultError::MathOverflow)?;
+        self.cost_basis = self.cost_basis.checked_add(cost).ok_or(MyVaultError::MathOverflow)?;
+        Ok(())
+    }
+    
+    // Move fee shares into the position. Shares cannot be transferred, so
+    // whatever the owner's share account holds beyond the position was
+    // minted to it as a fee recipient, now or before the recipient changed.
+    fn collect_fee_shares(&mut self, balance: u64) {
+        self.shares = self.shares.max(balance);
+    }
+    
+    fn record_withdraw(&mut self, assets: u64) -> Result<()> {
+        self.withdrawn = self.withdrawn.checked_add(assets).ok_or(MyVaultError::MathOverflow)?;
+        Ok(())
+    }
+}
+
+// Withdrawal request of an investor, one open request at a time
+#[account]
+pub struct WithdrawRequest {
+    pub vault: Pubkey,   // Vault
+    pub owner: Pubkey,   // Investor
+    pub epoch: u64,      // Epoch the request is settled in
+    pub shares: u64,     // Escrowed shares
+    pub cost_basis: u64, // Cost of the escrowed shares, restored on cancel
+    pub bump: u8,        // PDA bump
+}
+
+// Settlement of an epoch's withdrawal requests
+#[account]
+pub struct WithdrawEpoch {
+    pub vault: Pubkey,       // Vault
+    pub epoch: u64,          // Epoch number
+    pub shares: u64,         // Shares not yet claimed
+    pub assets: u64,         // Assets not yet claimed
+    pub processed_at: u64,   // Settlement timestamp
+    pub bump: u8,            // PDA bump
+}
+
+impl WithdrawEpoch {
+    // Take a request's pro rata share of what is left of the epoch, so the
+    // last claim takes any rounding remainder
+    fn claim(&mut self, shares: u64) -> Result<u64> {
+        let assets = math::mul_div_u64(shares, self.assets, self.shares, Rounding::Down)?;
+        self.shares = self.shares.checked_sub(shares).ok_or(MyVaultError::MathOverflow)?;
+        self.assets = self.assets.checked_sub(assets).ok_or(MyVaultError::MathOverflow)?;
+        Ok(assets)
+    }
+}
+
+// Strategy account structure
+#[account]
+#[derive(Default)]
+pub struct Strategy {
+    pub authority: Pubkey,                 // Admin
+    pub vault: Pubkey,                     // Associated vault
+    pub strategy_enabled: bool,            // Strategy enabled
+    pub signal_timeout: u64,               // Signal timeout (seconds)
+    pub last_signal_timestamp: u64,        // Last signal timestamp
+    pub trading_pairs: Vec<TradingPair>,   // Trading pair list
+    pub oracles: Vec<Pubkey>,              // Registered oracles allowed to send signals
+    pub signal_signer: Option<Pubkey>,     // Key signing relayed signals
+    pub last_signal_nonce: u64,            // Highest signal nonce executed
+    pub signal_nonce_bitmap: u64,          // Used nonces within NONCE_WINDOW of the highest
+    pub swap_programs: Vec<SwapProgram>,   // Allowlisted swap programs
+    pub max_price_age: u64,                // Price feed staleness limit (seconds)
+    pub base_decimals: u8,                 // Base token decimals
+    pub base_pyth_feed_id: Option<[u8; 32]>, // Pyth feed of the base token
+    pub max_confidence_bps: u64,           // Widest accepted Pyth confidence interval
+    pub max_pyth_price_age: u64,           // Pyth update staleness limit (seconds)
+    pub max_liquidation_deviation_bps: u16, // Widest oracle deviation of liquidation sales
+    pub bump: u8,                          // PDA bump
+}
+
+impl Strategy {
+    // Signals are valid for signal_timeout seconds after issue; issuer clocks
+    // may run up to MAX_SIGNAL_CLOCK_SKEW seconds ahead of the cluster
+    pub fn check_signal_age(&self, issued_at: u64, now: u64) -> Result<()> {
+        require!(
+            issued_at <= now.saturating_add(MAX_SIGNAL_CLOCK_SKEW),
+            MyVaultError::InvalidSignalTimestamp
+        );
+        require!(
+            now.saturating_sub(issued_at) <= self.signal_timeout,
+            MyVaultError::SignalExpired
+        );
+        Ok(())
+    }
+    
+    // Registered oracles may submit signals directly; anyone else must relay a
+    // signal signed by signal_signer. Returns the key that authorized the signal.
+    pub fn authorize_signal<M: AnchorSerialize>(
+        &self,
+        submitter: &Pubkey,
+        instructions: &AccountInfo,
+        message: &M,
+    ) -> Result<Pubkey> {
+        if self.oracles.contains(submitter) {
+            return Ok(*submitter);
+        }
+        
+        let signal_signer = self.signal_signer.ok_or(MyVaultError::OracleNotRegistered)?;
+        ed25519::verify_preceding_signature(instructions, &signal_signer, &message.try_to_vec()?)?;
+        Ok(signal_signer)
+    }
+    
+    // Allowlist entry of a swap program
+    pub fn allowed_swap_program(&self, program_id: &Pubkey) -> Result<SwapProgram> {
+        self.swap_programs
+            .iter()
+            .find(|p| p.program_id == *program_id)
+            .copied()
+            .ok_or_else(|| error!(MyVaultError::SwapProgramNotAllowed))
+    }
+    
+    // Mark a signal nonce as used. Bit i of the bitmap records nonce
+    // last_signal_nonce - i, so nonces may arrive out of order within the window.
+    pub fn consume_nonce(&mut self, nonce: u64) -> Result<()> {
+        if nonce > self.last_signal_nonce {
+            let shift = nonce - self.last_signal_nonce;
+            self.signal_nonce_bitmap = if shift >= NONCE_WINDOW {
+                0
+            } else {
+                self.signal_nonce_bitmap << shift
+            };
+            self.signal_nonce_bitmap |= 1;
+            self.last_signal_nonce = nonce;
+        } else {
+            let offset = self.last_signal_nonce - nonce;
+            require!(offset < NONCE_WINDOW, MyVaultError::SignalNonceTooOld);
+            
+            let bit = 1u64 << offset;
+            require!(
+                self.signal_nonce_bitmap & bit == 0,
+                MyVaultError::SignalReplayed
+            );
+            self.signal_nonce_bitmap |= bit;
+        }
+        Ok(())
+    }
+    
+    // Price of a pair in base token units per token unit, scaled by
+    // PRICE_SCALE, from the pair's price source
+    pub fn pair_price(
+        &self,
+        vault: &Vault,
+        pair: &TradingPair,
+        price_source: Option<&AccountInfo>,
+        base_price_source: Option<&AccountInfo>,
+        now: u64,
+    ) -> Result<u128> {
+        let price_source = price_source.ok_or(MyVaultError::MissingPriceSource)?;
+        match pair.price_source {
+            PriceSource::PriceFeed => load_price_feed(vault, pair, price_source)?.get_price(now, self.max_price_age),
+            PriceSource::Pyth { feed_id } => {
+                let load = |account: &AccountInfo, feed_id: &[u8; 32]| {
+                    pyth::load_price(account, feed_id, now as i64, self.max_pyth_price_age, self.max_confidence_bps)
+                };
+                let token_price = load(price_source, &feed_id)?;
+                let base_price = match &self.base_pyth_feed_id {
+                    Some(base_feed_id) => Some(load(
+                        base_price_source.ok_or(MyVaultError::MissingPriceSource)?,
+                        base_feed_id,
+                    )?),
+                    None => None,
+                };
+                pyth::price_in_base(&token_price, pair.token_decimals, base_price.as_ref(), self.base_decimals)
+            }
+        }
+    }
+    
+    // Value of an amount of a pair's token in base token units at the pair's
+    // price source, rounded down
+    pub fn pair_value(
+        &self,
+        vault: &Vault,
+        pair: &TradingPair,
+        amount: u64,
+        price_source: Option<&AccountInfo>,
+        base_price_source: Option<&AccountInfo>,
+        now: u64,
+    ) -> Result<u64> {
+        match pair.price_source {
+            PriceSource::PriceFeed => {
+                let price_source = price_source.ok_or(MyVaultError::MissingPriceSource)?;
+                load_price_feed(vault, pair, price_source)?.token_value_in_base(amount, now, self.max_price_age)
+            }
+            PriceSource::Pyth { .. } => {
+                value_in_base(amount, self.pair_price(vault, pair, price_source, base_price_source, now)?)
+            }
+        }
+    }
+}
+
+// The PriceFeed account pricing a pair's token in the vault base token
+fn load_price_feed(vault: &Vault, pair: &TradingPair, account: &AccountInfo) -> Result<PriceFeed> {
+    require_keys_eq!(*account.owner, crate::ID, MyVaultError::InvalidPriceFeed);
+    let feed = PriceFeed::try_deserialize(&mut &account.try_borrow_data()?[..])?;
+    require!(
+        feed.token_mint == pair.token_mint && feed.base_mint == vault.base_token_mint,
+        MyVaultError::InvalidPriceFeed
+    );
+    Ok(feed)
+}
+
+// Price of a token in the vault base token, seeded by (token mint, base mint)
+#[account]
+pub struct PriceFeed {
+    pub token_mint: Pubkey,            // Priced token
+    pub base_mint: Pubkey,             // Vault base token the price is quoted in
+    pub price: u128,                   // Base token units per token unit, scaled by 1e18
+    pub last_update_timestamp: u64,    // Last update time, 0 if never updated
+    pub bump: u8,                      // PDA bump
+}
+
+impl PriceFeed {
+    pub fn set_price(&mut self, price: u128, now: u64) -> Result<()> {
+        require!(price > 0, MyVaultError::InvalidPrice);
+        
+        self.price = price;
+        self.last_update_timestamp = now;
+        
+        emit!(PriceUpdated {
+            token_mint: self.token_mint,
+            base_mint: self.base_mint,
+            price,
+            timestamp: now,
+        });
+        Ok(())
+    }
+    
+    // Current price, rejecting feeds never updated or older than max_price_age
+    pub fn get_price(&self, now: u64, max_price_age: u64) -> Result<u128> {
+        require!(
+            self.last_update_timestamp > 0,
+            MyVaultError::PriceNotAvailable
+        );
+        require!(
+            now.saturating_sub(self.last_update_timestamp) <= max_price_age,
+            MyVaultError::PriceStale
+        );
+        Ok(self.price)
+    }
+    
+    // Value of a token amount in base token units at the current price,
+    // rounded down
+    pub fn token_value_in_base(&self, amount: u64, now: u64, max_price_age: u64) -> Result<u64> {
+        value_in_base(amount, self.get_price(now, max_price_age)?)
+    }
+}
+
+// Initialize vault instruction
+#[derive(Accounts)]
+#[instruction(name: String)]
+pub struct InitializeVault<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + 32 + 32 + 32 + 32 + 4 + name.len() + 32 + 2 + 2 + 32 + 16 + 8 + 8 + 8 + 8 + 1 + 8 + 8 + 8 + 8 + 8 + 2 + 1,
+        seeds = [VAULT_SEED, base_token_mint.key().as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, Vault>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + 32 + 32 + 1 + 8 + 8 + 4 + (32 + 1 + 8 + 8 + 1 + 33 + 1 + 2) * 5 + 4 + 32 * MAX_ORACLES + 1 + 32 + 8 + 8 + 4 + (32 + 1) * MAX_SWAP_PROGRAMS + 8 + 1 + 33 + 8 + 8 + 2 + 1, // Reduce to 5 trading pair spaces
+        seeds = [STRATEGY_SEED, vault.key().as_ref()],
+        bump
+    )]
+    pub strategy: Account<'info, Strategy>,
+    
+    pub base_token_mint: Account<'info, Mint>,
+    
+    #[account(
+        init,
+        payer = authority,
+        token::mint = base_token_mint,
+        token::authority = vault_authority,
+        seeds = ["base_token".as_bytes(), vault.key().as_ref()],
+        bump
+    )]
+    pub vault_base_token: Account<'info, TokenAccount>,
+    
+    /// CHECK: PDA as vault authority
+    #[account(
+        seeds = [VAULT_SEED, base_token_mint.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: AccountInfo<'info>,
+    
+    #[account(
+        init,
+        payer = authority,
+        mint::decimals = base_token_mint.decimals,
+        mint::authority = vault_authority,
+        mint::freeze_authority = vault_authority,
+        seeds = ["shares".as_bytes(), vault.key().as_ref()],
+        bump
+    )]
+    pub vault_shares: Account<'info, Mint>,
+    
+    // Holds shares of withdrawal requests until their epoch is processed
+    #[account(
+        init,
+        payer = authority,
+        token::mint = vault_shares,
+        token::authority = vault_authority,
+        seeds = [WITHDRAW_ESCROW_SEED, vault.key().as_ref()],
+        bump
+    )]
+    pub withdraw_escrow: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
 }
 
-// Jupiter route data
-#[derive(AnchorSerialize, AnchorDeserialize)]
-pub struct JupiterRouteData {
-    pub token_mint: Pubkey,
-    pub jupiter_user_account: Pubkey,
-    pub route_data: Vec<u8>,
+// Set trading pair instruction
+#[derive(Accounts)]
+pub struct SetTradingPair<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(mut)]
+    pub strategy: Account<'info, Strategy>,
+    
+    pub token_mint: Account<'info, Mint>,
+    
+    pub vault: Account<'info, Vault>,
 }
 
-// Event definition
-#[event]
-pub struct SignalReceived {
-    pub signal_type: SignalType,
-    pub token_mint: Pubkey,
-    pub timestamp: u64,
+// Update strategy settings instruction
+#[derive(Accounts)]
+pub struct UpdateStrategy<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(mut)]
+    pub strategy: Account<'info, Strategy>,
+    
+    pub vault: Account<'info, Vault>,
 }
 
-#[event]
-pub struct TradeExecuted {
-    pub signal_type: SignalType,
-    pub token_mint: Pubkey,
-    pub amount: u64,
-    pub result: u64,
+// Execute trade instruction
+#[derive(Accounts)]
+pub struct ExecuteTrade<'info> {
+    #[account(mut)]
+    pub oracle: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [STRATEGY_SEED, vault.key().as_ref()],
+        bump = strategy.bump
+    )]
+    pub strategy: Account<'info, Strategy>,
+    
+    #[account(mut)]
+    pub vault: Account<'info, Vault>,
+    
+    #[account(
+        mut,
+        address = vault.base_token_account
+    )]
+    pub vault_base_token: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = vault_authority
+    )]
+    pub vault_token_account: Account<'info, TokenAccount>,
+    
+    /// CHECK: PDA used in transaction
+    #[account(
+        seeds = [VAULT_SEED, vault.base_token_mint.as_ref()],
+        bump
+    )]
+    pub vault_authority: AccountInfo<'info>,
+    
+    pub token_mint: Account<'info, Mint>,
+    
+    /// CHECK: Swap program, checked against the strategy allowlist
+    #[account(executable)]
+    pub swap_program: AccountInfo<'info>,
+    
+    /// CHECK: Instructions sysvar, used to verify signed signals
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    
+    /// CHECK: Pair price source (PriceFeed or Pyth account), checked in
+    /// Strategy::pair_price. Required when the pair has an oracle deviation guard.
+    pub price_source: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Pyth price account of the base token, checked in Strategy::pair_price
+    pub base_price_source: Option<UncheckedAccount<'info>>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    
+    // Remaining accounts are the accounts of the trading pair's swap venue
+    // instruction, with vault_authority as the user transfer authority and
+    // the vault token accounts as source and destination
 }
 
-#[event]
-pub struct Deposited {
-    pub user: Pubkey,
-    pub amount: u64,
-    pub shares: u64,
+// Execute swap instruction
+#[derive(Accounts)]
+pub struct ExecuteSwap<'info> {
+    #[account(mut)]
+    pub oracle: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [STRATEGY_SEED, vault.key().as_ref()],
+        bump = strategy.bump
+    )]
+    pub strategy: Account<'info, Strategy>,
+    
+    #[account(mut)]
+    pub vault: Account<'info, Vault>,
+    
+    #[account(
+        mut,
+        token::mint = input_mint,
+        token::authority = vault_authority
+    )]
+    pub vault_input_token: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        token::mint = output_mint,
+        token::authority = vault_authority
+    )]
+    pub vault_output_token: Account<'info, TokenAccount>,
+    
+    // Base balance the allocation of swaps into a pair is measured against
+    #[account(address = vault.base_token_account)]
+    pub vault_base_token: Account<'info, TokenAccount>,
+    
+    /// CHECK: PDA used in transaction
+    #[account(
+        seeds = [VAULT_SEED, vault.base_token_mint.as_ref()],
+        bump
+    )]
+    pub vault_authority: AccountInfo<'info>,
+    
+    pub input_mint: Account<'info, Mint>,
+    
+    pub output_mint: Account<'info, Mint>,
+    
+    /// CHECK: Swap program, checked against the strategy allowlist
+    #[account(executable)]
+    pub swap_program: AccountInfo<'info>,
+    
+    /// CHECK: Instructions sysvar, used to verify signed signals
+    #[account(address = sysvar_instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    
+    /// CHECK: Pair price source (PriceFeed or Pyth account), checked in
+    /// Strategy::pair_price. Required when the pair has an oracle deviation
+    /// guard, and for rotations between pairs, where it prices the input pair.
+    pub price_source: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Pyth price account of the base token, checked in Strategy::pair_price
+    pub base_price_source: Option<UncheckedAccount<'info>>,
+    
+    pub token_program: Program<'info, Token>,
+    
+    // Remaining accounts are the accounts of the swap venue instruction, as
+    // for ExecuteTrade
 }
 
-#[event]
-pub struct Withdrawn {
-    pub user: Pubkey,
-    pub amount: u64,
-    pub shares: u64,
+// Initialize price feed instruction
+#[derive(Accounts)]
+pub struct InitializePriceFeed<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub vault: Account<'info, Vault>,
+    
+    pub token_mint: Account<'info, Mint>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + 32 + 32 + 16 + 8 + 1,
+        seeds = [PRICE_FEED_SEED, token_mint.key().as_ref(), vault.base_token_mint.as_ref()],
+        bump
+    )]
+    pub price_feed: Account<'info, PriceFeed>,
+    
+    pub system_program: Program<'info, System>,
 }
 
-// Error code
-#[error_code]
-pub enum MyVaultError {
-    #[msg("Unauthorized operation")]
-    Unauthorized,
+// Update price instruction
+#[derive(Accounts)]
+pub struct UpdatePrice<'info> {
+    pub oracle: Signer<'info>,
+    
+    #[account(
+        seeds = [STRATEGY_SEED, vault.key().as_ref()],
+        bump = strategy.bump
+    )]
+    pub strategy: Account<'info, Strategy>,
+    
+    pub vault: Account<'info, Vault>,
+    
+    #[account(
+        mut,
+        seeds = [PRICE_FEED_SEED, price_feed.token_mint.as_ref(), vault.base_token_mint.as_ref()],
+        bump = price_feed.bump
+    )]
+    pub price_feed: Account<'info, PriceFeed>,
+}
+
+// Batch price update instruction
+#[derive(Accounts)]
+pub struct UpdatePricesBatch<'info> {
+    pub oracle: Signer<'info>,
+    
+    #[account(
+        seeds = [STRATEGY_SEED, vault.key().as_ref()],
+        bump = strategy.bump
+    )]
+    pub strategy: Account<'info, Strategy>,
+    
+    pub vault: Account<'info, Vault>,
+    
+    // Remaining accounts are the price feeds to update, writable
+}
+
+// Deposit instruction
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(mut)]
+    pub vault: Account<'info, Vault>,
+    
+    #[account(
+        seeds = [STRATEGY_SEED, vault.key().as_ref()],
+        bump = strategy.bump
+    )]
+    pub strategy: Account<'info, Strategy>,
+    
+    #[account(
+        mut,
+        address = vault.base_token_account
+    )]
+    pub vault_base_token: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = ["shares".as_bytes(), vault.key().as_ref()],
+        bump
+    )]
+    pub vault_shares: Account<'info, Mint>,
+    
+    /// CHECK: PDA used in transaction
+    #[account(
+        seeds = [VAULT_SEED, vault.base_token_mint.as_ref()],
+        bump
+    )]
+    pub vault_authority: AccountInfo<'info>,
+    
+    #[account(mut)]
+    pub user_token: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        associated_token::mint = vault_shares,
+        associated_token::authority = authority
+    )]
+    pub user_shares: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [POSITION_SEED, vault.key().as_ref(), authority.key().as_ref()],
+        bump = position.bump
+    )]
+    pub position: Account<'info, InvestorPosition>,
+    
+    #[account(
+        mut,
+        associated_token::mint = vault_shares,
+        associated_token::authority = vault.fee_recipient
+    )]
+    pub fee_recipient_shares: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    
+    // Remaining accounts are the NAV accounts, see nav::total_assets
+}
+
+impl Deposit<'_> {
+    fn total_assets(&self, nav_accounts: &[AccountInfo]) -> Result<u64> {
+        nav::total_assets(
+            &self.strategy,
+            &self.vault,
+            &self.vault_authority.key(),
+            self.vault_base_token.amount,
+            nav_accounts,
+            Clock::get()?.unix_timestamp as u64,
+        )
+    }
+    
+    // Accrue fees, moving any fee shares the owner holds into its position
+    fn accrue_fees(&mut self, total_assets: u64, vault_authority_bump: u8) -> Result<()> {
+        accrue_vault_fees(
+            &mut self.vault,
+            &mut self.vault_shares,
+            &self.fee_recipient_shares,
+            &self.vault_authority,
+            &self.token_program,
+            total_assets,
+            vault_authority_bump,
+        )?;
+        self.user_shares.reload()?;
+        self.position.collect_fee_shares(self.user_shares.amount);
+        Ok(())
+    }
+    
+    // Check the vault limits for depositing assets for shares at NAV, on
+    // top of the shares the position holds
+    fn check_deposit_limits(&self, assets: u64, shares: u64, total_assets: u64) -> Result<()> {
+        let investor_assets = nav::convert_to_assets(
+            self.position.shares.checked_add(shares).ok_or(MyVaultError::MathOverflow)?,
+            total_assets.checked_add(assets).ok_or(MyVaultError::MathOverflow)?,
+            self.vault_shares.supply.checked_add(shares).ok_or(MyVaultError::MathOverflow)?,
+            Rounding::Down,
+        )?;
+        self.vault.check_deposit_limits(assets, total_assets, investor_assets)
+    }
+    
+    // Transfer assets from the depositor to the vault and mint them shares
+    fn deposit_and_mint(&mut self, assets: u64, shares: u64, vault_authority_bump: u8) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp as u64;
+        self.position.record_deposit(
+            assets,
+            shares,
+            now.saturating_add(self.vault.lockup_period),
+            now,
+        )?;
+        
+        // Transfer tokens to vault
+        let cpi_accounts = Transfer {
+            from: self.user_token.to_account_info(),
+            to: self.vault_base_token.to_account_info(),
+            authority: self.authority.to_account_info(),
+        };
+        
+        let cpi_program = self.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(
+            cpi_program,
+            cpi_accounts,
+        );
+        
+        token::transfer(cpi_ctx, assets)?;
+        
+        // Mint shares tokens
+        let vault_authority_seeds = &[
+            VAULT_SEED,
+            self.vault.base_token_mint.as_ref(),
+            &[vault_authority_bump],
+        ];
+        
+        let user_shares = self.user_shares.to_account_info();
+        let vault_shares = self.vault_shares.to_account_info();
+        let vault_authority = self.vault_authority.to_account_info();
+        let token_program = self.token_program.to_account_info();
+        thaw_shares(&user_shares, &vault_shares, &vault_authority, &token_program, vault_authority_seeds)?;
+        
+        let cpi_accounts = token::MintTo {
+            mint: vault_shares.clone(),
+            to: user_shares.clone(),
+            authority: vault_authority.clone(),
+        };
+        
+        let cpi_program = token_program.clone();
+        let cpi_signer_seeds = &[&vault_authority_seeds[..]];
+        
+        let cpi_ctx = CpiContext::new_with_signer(
+            cpi_program,
+            cpi_accounts,
+            cpi_signer_seeds,
+        );
+        
+        token::mint_to(cpi_ctx, shares)?;
+        freeze_shares(&user_shares, &vault_shares, &vault_authority, &token_program, vault_authority_seeds)
+    }
+}
+
+// Withdraw instruction
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
     
-    #[msg("Strategy not enabled")]
-    StrategyNotEnabled,
+    #[account(mut)]
+    pub vault: Account<'info, Vault>,
     
-    #[msg("Trading pair not active")]
-    TradingPairNotActive,
+    #[account(
+        seeds = [STRATEGY_SEED, vault.key().as_ref()],
+        bump = strategy.bump
+    )]
+    pub strategy: Account<'info, Strategy>,
     
-    #[msg("Allocation exceeds maximum")]
-    AllocationExceedsMaximum,
+    #[account(
+        mut,
+        address = vault.base_token_account
+    )]
+    pub vault_base_token: Account<'info, TokenAccount>,
     
-    #[msg("Swap amount exceeds allocation")]
-    SwapAmountExceedsAllocation,
+    #[account(
+        mut,
+        seeds = ["shares".as_bytes(), vault.key().as_ref()],
+        bump
+    )]
+    pub vault_shares: Account<'info, Mint>,
     
-    #[msg("Invalid allocation percentage")]
-    InvalidAllocation,
+    /// CHECK: PDA used in transaction
+    #[account(
+        seeds = [VAULT_SEED, vault.base_token_mint.as_ref()],
+        bump
+    )]
+    pub vault_authority: AccountInfo<'info>,
     
-    #[msg("Invalid percentage value")]
-    InvalidPercentage,
+    #[account(mut)]
+    pub user_token: Account<'info, TokenAccount>,
     
-    #[msg("Insufficient shares")]
-    InsufficientShares,
+    #[account(
+        mut,
+        associated_token::mint = vault_shares,
+        associated_token::authority = authority
+    )]
+    pub user_shares: Account<'info, TokenAccount>,
     
-    #[msg("Insufficient vault balance")]
-    InsufficientVaultBalance,
+    #[account(
+        mut,
+        seeds = [POSITION_SEED, vault.key().as_ref(), authority.key().as_ref()],
+        bump = position.bump
+    )]
+    pub position: Account<'info, InvestorPosition>,
     
-    #[msg("Only specified investor can operate")]
-    OnlyInvestorAllowed,
+    #[account(
+        mut,
+        associated_token::mint = vault_shares,
+        associated_token::authority = vault.fee_recipient
+    )]
+    pub fee_recipient_shares: Account<'info, TokenAccount>,
     
-    #[msg("Account not found")]
-    AccountNotFound,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
     
-    #[msg("Cannot withdraw 0 amount of assets")]
-    InvalidWithdrawAmount,
-}
-
-// Vault account structure
-#[account]
-#[derive(Default)]
-pub struct Vault {
-    pub base_token_mint: Pubkey,     // Base token mint
-    pub base_token_account: Pubkey,  // Base token account
-    pub authority: Pubkey,           // Admin
-    pub strategy: Pubkey,            // Strategy account
-    pub name: String,                // Vault name
-    pub investor: Pubkey,            // Investor
-    pub bump: u8,                    // PDA bump
+    // Remaining accounts are the NAV accounts, see nav::total_assets
 }
 
-// Strategy account structure
-#[account]
-pub struct Strategy {
-    pub authority: Pubkey,                 // Admin
-    pub vault: Pubkey,                     // Associated vault
-    pub strategy_enabled: bool,            // Strategy enabled
-    pub signal_timeout: u64,               // Signal timeout (seconds)
-    pub last_signal_timestamp: u64,        // Last signal timestamp
-    pub trading_pairs: Vec<TradingPair>,   // Trading pair list
-    pub bump: u8,                          // PDA bump
+impl Withdraw<'_> {
+    fn total_assets(&self, nav_accounts: &[AccountInfo]) -> Result<u64> {
+        nav::total_assets(
+            &self.strategy,
+            &self.vault,
+            &self.vault_authority.key(),
+            self.vault_base_token.amount,
+            nav_accounts,
+            Clock::get()?.unix_timestamp as u64,
+        )
+    }
+    
+    // Accrue fees, moving any fee shares the owner holds into its position
+    fn accrue_fees(&mut self, total_assets: u64, vault_authority_bump: u8) -> Result<()> {
+        accrue_vault_fees(
+            &mut self.vault,
+            &mut self.vault_shares,
+            &self.fee_recipient_shares,
+            &self.vault_authority,
+            &self.token_program,
+            total_assets,
+            vault_authority_bump,
+        )?;
+        self.user_shares.reload()?;
+        self.position.collect_fee_shares(self.user_shares.amount);
+        Ok(())
+    }
+    
+    // Early exit fee on withdrawing assets now, see Vault::early_exit_fee
+    fn early_exit_fee(&self, assets: u64) -> Result<u64> {
+        let fee = self.vault.early_exit_fee(&self.position, assets, Clock::get()?.unix_timestamp as u64)?;
+        if fee > 0 {
+            msg!("Early exit fee: {}", fee);
+        }
+        Ok(fee)
+    }
+    
+    // Assets paid for withdrawing `assets` now, less the early exit fee
+    fn assets_after_early_exit_fee(&self, assets: u64) -> Result<u64> {
+        let fee = self.early_exit_fee(assets)?;
+        assets.checked_sub(fee).ok_or_else(|| error!(MyVaultError::MathOverflow))
+    }
+    
+    // Check the withdrawer's position left after burning shares at NAV
+    fn check_remaining_balance(&self, shares: u64, total_assets: u64) -> Result<()> {
+        let remaining_shares = self.position.shares.saturating_sub(shares);
+        let remaining_assets = nav::convert_to_assets(remaining_shares, total_assets, self.vault_shares.supply, Rounding::Down)?;
+        self.vault.check_remaining_balance(remaining_shares, remaining_assets)
+    }
+    
+    // Burn the withdrawer's shares and transfer them assets from the vault
+    fn burn_and_pay(&mut self, shares: u64, assets: u64, vault_authority_bump: u8) -> Result<()> {
+        self.position.remove_shares(shares)?;
+        self.position.record_withdraw(assets)?;
+        
+        let vault_authority_seeds = &[
+            VAULT_SEED,
+            self.vault.base_token_mint.as_ref(),
+            &[vault_authority_bump],
+        ];
+        
+        // First burn shares
+        let user_shares = self.user_shares.to_account_info();
+        let vault_shares = self.vault_shares.to_account_info();
+        let vault_authority = self.vault_authority.to_account_info();
+        let token_program = self.token_program.to_account_info();
+        thaw_shares(&user_shares, &vault_shares, &vault_authority, &token_program, vault_authority_seeds)?;
+        
+        let cpi_accounts = token::Burn {
+            mint: vault_shares.clone(),
+            from: user_shares.clone(),
+            authority: self.authority.to_account_info(),
+        };
+        
+        let cpi_program = token_program.clone();
+        let cpi_ctx = CpiContext::new(
+            cpi_program,
+            cpi_accounts,
+        );
+        
+        token::burn(cpi_ctx, shares)?;
+        freeze_shares(&user_shares, &vault_shares, &vault_authority, &token_program, vault_authority_seeds)?;
+        
+        // Transfer assets to user
+        let cpi_accounts = Transfer {
+            from: self.vault_base_token.to_account_info(),
+            to: self.user_token.to_account_info(),
+            authority: self.vault_authority.to_account_info(),
+        };
+        
+        let cpi_program = self.token_program.to_account_info();
+        let cpi_signer_seeds = &[&vault_authority_seeds[..]];
+        
+        let cpi_ctx = CpiContext::new_with_signer(
+            cpi_program,
+            cpi_accounts,
+            cpi_signer_seeds,
+        );
+        
+        token::transfer(cpi_ctx, assets)
+    }
 }
 
-// Initialize vault instruction
+// Request withdraw instruction
 #[derive(Accounts)]
-#[instruction(name: String)]
-pub struct InitializeVault<'info> {
+pub struct RequestWithdraw<'info> {
     #[account(mut)]
     pub authority: Signer<'info>,
     
+    #[account(mut)]
+    pub vault: Account<'info, Vault>,
+    
     #[account(
         init,
         payer = authority,
-        space = 8 + 32 + 32 + 32 + 32 + 4 + name.len() + 32 + 1,
-        seeds = [VAULT_SEED, base_token_mint.key().as_ref()],
+        space = 8 + 32 + 32 + 8 + 8 + 8 + 1,
+        seeds = [WITHDRAW_REQUEST_SEED, vault.key().as_ref(), authority.key().as_ref()],
         bump
     )]
-    pub vault: Account<'info, Vault>,
+    pub withdraw_request: Account<'info, WithdrawRequest>,
     
     #[account(
-        init,
-        payer = authority,
-        space = 8 + 32 + 32 + 1 + 8 + 8 + 4 + (32 + 1 + 8 + 8) * 5 + 1, // Reduce to 5 trading pair spaces
         seeds = [STRATEGY_SEED, vault.key().as_ref()],
+        bump = strategy.bump
+    )]
+    pub strategy: Account<'info, Strategy>,
+    
+    #[account(address = vault.base_token_account)]
+    pub vault_base_token: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [WITHDRAW_ESCROW_SEED, vault.key().as_ref()],
+        bump
+    )]
+    pub withdraw_escrow: Account<'info, TokenAccount>,
+    
+    #[account(
+        seeds = ["shares".as_bytes(), vault.key().as_ref()],
+        bump
+    )]
+    pub vault_shares: Account<'info, Mint>,
+    
+    /// CHECK: PDA used in transaction
+    #[account(
+        seeds = [VAULT_SEED, vault.base_token_mint.as_ref()],
         bump
     )]
+    pub vault_authority: AccountInfo<'info>,
+    
+    #[account(
+        mut,
+        associated_token::mint = withdraw_escrow.mint,
+        associated_token::authority = authority
+    )]
+    pub user_shares: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [POSITION_SEED, vault.key().as_ref(), authority.key().as_ref()],
+        bump = position.bump
+    )]
+    pub position: Account<'info, InvestorPosition>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    
+    // Remaining accounts are the NAV accounts, see nav::total_assets
+}
+
+impl RequestWithdraw<'_> {
+    fn total_assets(&self, nav_accounts: &[AccountInfo]) -> Result<u64> {
+        nav::total_assets(
+            &self.strategy,
+            &self.vault,
+            &self.vault_authority.key(),
+            self.vault_base_token.amount,
+            nav_accounts,
+            Clock::get()?.unix_timestamp as u64,
+        )
+    }
+    
+    // Check the requester's position left after escrowing shares, valued at
+    // the current NAV including fees accrued since the last accrual
+    fn check_remaining_balance(&self, shares: u64, total_assets: u64) -> Result<()> {
+        let remaining_shares = self.position.shares.saturating_sub(shares);
+        let total_shares = fees::total_shares_after_accrual(
+            &self.vault,
+            total_assets,
+            self.vault_shares.supply,
+            Clock::get()?.unix_timestamp as u64,
+        )?;
+        let remaining_assets = nav::convert_to_assets(remaining_shares, total_assets, total_shares, Rounding::Down)?;
+        self.vault.check_remaining_balance(remaining_shares, remaining_assets)
+    }
+}
+
+// Process withdrawals instruction
+#[derive(Accounts)]
+pub struct ProcessWithdrawals<'info> {
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+    
+    #[account(mut)]
+    pub vault: Account<'info, Vault>,
+    
+    #[account(
+        seeds = [STRATEGY_SEED, vault.key().as_ref()],
+        bump = strategy.bump
+    )]
     pub strategy: Account<'info, Strategy>,
     
-    pub base_token_mint: Account<'info, Mint>,
+    #[account(address = vault.base_token_account)]
+    pub vault_base_token: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = ["shares".as_bytes(), vault.key().as_ref()],
+        bump
+    )]
+    pub vault_shares: Account<'info, Mint>,
+    
+    /// CHECK: PDA used in transaction
+    #[account(
+        seeds = [VAULT_SEED, vault.base_token_mint.as_ref()],
+        bump
+    )]
+    pub vault_authority: AccountInfo<'info>,
+    
+    #[account(
+        mut,
+        seeds = [WITHDRAW_ESCROW_SEED, vault.key().as_ref()],
+        bump
+    )]
+    pub withdraw_escrow: Account<'info, TokenAccount>,
     
     #[account(
         init,
-        payer = authority,
-        token::mint = base_token_mint,
-        token::authority = vault_authority,
-        seeds = ["base_token".as_bytes(), vault.key().as_ref()],
+        payer = keeper,
+        space = 8 + 32 + 8 + 8 + 8 + 8 + 1,
+        seeds = [WITHDRAW_EPOCH_SEED, vault.key().as_ref(), &vault.withdraw_epoch.to_le_bytes()],
+        bump
+    )]
+    pub withdraw_epoch: Account<'info, WithdrawEpoch>,
+    
+    #[account(
+        mut,
+        associated_token::mint = vault_shares,
+        associated_token::authority = vault.fee_recipient
+    )]
+    pub fee_recipient_shares: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    
+    // Remaining accounts are the NAV accounts, see nav::total_assets
+}
+
+impl ProcessWithdrawals<'_> {
+    fn total_assets(&self, nav_accounts: &[AccountInfo]) -> Result<u64> {
+        nav::total_assets(
+            &self.strategy,
+            &self.vault,
+            &self.vault_authority.key(),
+            self.vault_base_token.amount,
+            nav_accounts,
+            Clock::get()?.unix_timestamp as u64,
+        )
+    }
+    
+    fn accrue_fees(&mut self, total_assets: u64, vault_authority_bump: u8) -> Result<()> {
+        accrue_vault_fees(
+            &mut self.vault,
+            &mut self.vault_shares,
+            &self.fee_recipient_shares,
+            &self.vault_authority,
+            &self.token_program,
+            total_assets,
+            vault_authority_bump,
+        )
+    }
+}
+
+// Claim withdrawal instruction
+#[derive(Accounts)]
+pub struct ClaimWithdrawal<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(mut)]
+    pub vault: Account<'info, Vault>,
+    
+    #[account(
+        mut,
+        close = authority,
+        seeds = [WITHDRAW_REQUEST_SEED, vault.key().as_ref(), authority.key().as_ref()],
+        bump = withdraw_request.bump
+    )]
+    pub withdraw_request: Account<'info, WithdrawRequest>,
+    
+    #[account(
+        mut,
+        seeds = [WITHDRAW_EPOCH_SEED, vault.key().as_ref(), &withdraw_request.epoch.to_le_bytes()],
+        bump = withdraw_epoch.bump
+    )]
+    pub withdraw_epoch: Account<'info, WithdrawEpoch>,
+    
+    #[account(
+        mut,
+        address = vault.base_token_account
+    )]
+    pub vault_base_token: Account<'info, TokenAccount>,
+    
+    /// CHECK: PDA used in transaction
+    #[account(
+        seeds = [VAULT_SEED, vault.base_token_mint.as_ref()],
         bump
     )]
-    pub vault_base_token: Account<'info, TokenAccount>,
+    pub vault_authority: AccountInfo<'info>,
+    
+    #[account(mut)]
+    pub user_token: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [POSITION_SEED, vault.key().as_ref(), authority.key().as_ref()],
+        bump = position.bump
+    )]
+    pub position: Account<'info, InvestorPosition>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+// Cancel withdrawal instruction
+#[derive(Accounts)]
+pub struct CancelWithdrawal<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(mut)]
+    pub vault: Account<'info, Vault>,
+    
+    #[account(
+        mut,
+        close = authority,
+        seeds = [WITHDRAW_REQUEST_SEED, vault.key().as_ref(), authority.key().as_ref()],
+        bump = withdraw_request.bump
+    )]
+    pub withdraw_request: Account<'info, WithdrawRequest>,
     
-    /// CHECK: PDA as vault authority
     #[account(
-        seeds = [VAULT_SEED, base_token_mint.key().as_ref()],
+        mut,
+        seeds = [WITHDRAW_ESCROW_SEED, vault.key().as_ref()],
         bump
     )]
-    pub vault_authority: AccountInfo<'info>,
+    pub withdraw_escrow: Account<'info, TokenAccount>,
     
     #[account(
-        init,
-        payer = authority,
-        mint::decimals = base_token_mint.decimals,
-        mint::authority = vault_authority,
         seeds = ["shares".as_bytes(), vault.key().as_ref()],
         bump
     )]
     pub vault_shares: Account<'info, Mint>,
     
-    pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
-    pub system_program: Program<'info, System>,
-    pub rent: Sysvar<'info, Rent>,
-}
-
-// Set trading pair instruction
-#[derive(Accounts)]
-pub struct SetTradingPair<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
+    /// CHECK: PDA used in transaction
+    #[account(
+        seeds = [VAULT_SEED, vault.base_token_mint.as_ref()],
+        bump
+    )]
+    pub vault_authority: AccountInfo<'info>,
     
-    #[account(mut)]
-    pub strategy: Account<'info, Strategy>,
+    #[account(
+        mut,
+        associated_token::mint = withdraw_escrow.mint,
+        associated_token::authority = authority
+    )]
+    pub user_shares: Account<'info, TokenAccount>,
     
-    pub token_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [POSITION_SEED, vault.key().as_ref(), authority.key().as_ref()],
+        bump = position.bump
+    )]
+    pub position: Account<'info, InvestorPosition>,
     
-    pub vault: Account<'info, Vault>,
+    pub token_program: Program<'info, Token>,
 }
 
-// Update strategy settings instruction
+// Fee accrual instructions
 #[derive(Accounts)]
-pub struct UpdateStrategy<'info> {
-    #[account(mut)]
+pub struct AccrueFees<'info> {
     pub authority: Signer<'info>,
     
     #[account(mut)]
-    pub strategy: Account<'info, Strategy>,
-    
     pub vault: Account<'info, Vault>,
-}
-
-// Execute trade instruction
-#[derive(Accounts)]
-pub struct ExecuteTrade<'info> {
-    #[account(mut)]
-    pub oracle: Signer<'info>,
     
-    #[account(mut)]
+    #[account(
+        seeds = [STRATEGY_SEED, vault.key().as_ref()],
+        bump = strategy.bump
+    )]
     pub strategy: Account<'info, Strategy>,
     
-    #[account(mut)]
-    pub vault: Account<'info, Vault>,
-    
-    #[account(mut)]
+    #[account(address = vault.base_token_account)]
     pub vault_base_token: Account<'info, TokenAccount>,
     
-    #[account(mut)]
-    pub vault_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = ["shares".as_bytes(), vault.key().as_ref()],
+        bump
+    )]
+    pub vault_shares: Account<'info, Mint>,
     
     /// CHECK: PDA used in transaction
     #[account(
@@ -848,123 +3898,584 @@ pub struct ExecuteTrade<'info> {
     )]
     pub vault_authority: AccountInfo<'info>,
     
-    pub token_mint: Account<'info, Mint>,
-    
-    /// CHECK: Jupiter will handle this account
-    #[account(mut)]
-    pub jupiter_user_token_account: AccountInfo<'info>,
-    
-    /// CHECK: Jupiter program
-    #[account(mut)]
-    pub jupiter_program: AccountInfo<'info>,
+    #[account(
+        mut,
+        associated_token::mint = vault_shares,
+        associated_token::authority = vault.fee_recipient
+    )]
+    pub fee_recipient_shares: Account<'info, TokenAccount>,
     
     pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
     
-    // Remaining accounts will be passed as remaining accounts to Jupiter
+    // Remaining accounts are the NAV accounts, see nav::total_assets
 }
 
-// Emergency exit instruction
-#[derive(Accounts)]
-pub struct EmergencyExit<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
+impl AccrueFees<'_> {
+    fn total_assets(&self, nav_accounts: &[AccountInfo]) -> Result<u64> {
+        nav::total_assets(
+            &self.strategy,
+            &self.vault,
+            &self.vault_authority.key(),
+            self.vault_base_token.amount,
+            nav_accounts,
+            Clock::get()?.unix_timestamp as u64,
+        )
+    }
     
-    #[account(mut)]
+    fn accrue_fees(&mut self, total_assets: u64, vault_authority_bump: u8) -> Result<()> {
+        accrue_vault_fees(
+            &mut self.vault,
+            &mut self.vault_shares,
+            &self.fee_recipient_shares,
+            &self.vault_authority,
+            &self.token_program,
+            total_assets,
+            vault_authority_bump,
+        )
+    }
+}
+
+// Vault view instructions
+#[derive(Accounts)]
+pub struct VaultView<'info> {
     pub vault: Account<'info, Vault>,
     
-    /// CHECK: PDA used in transaction
+    #[account(
+        seeds = [STRATEGY_SEED, vault.key().as_ref()],
+        bump = strategy.bump
+    )]
+    pub strategy: Account<'info, Strategy>,
+    
+    #[account(address = vault.base_token_account)]
+    pub vault_base_token: Account<'info, TokenAccount>,
+    
+    #[account(
+        seeds = ["shares".as_bytes(), vault.key().as_ref()],
+        bump
+    )]
+    pub vault_shares: Account<'info, Mint>,
+    
+    /// CHECK: PDA used to derive vault token accounts
     #[account(
         seeds = [VAULT_SEED, vault.base_token_mint.as_ref()],
         bump
     )]
     pub vault_authority: AccountInfo<'info>,
     
-    /// CHECK: Jupiter program
-    #[account(mut)]
-    pub jupiter_program: AccountInfo<'info>,
-    
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
+    // Remaining accounts are the NAV accounts, see nav::total_assets
+}
+
+impl VaultView<'_> {
+    fn total_assets(&self, nav_accounts: &[AccountInfo]) -> Result<u64> {
+        nav::total_assets(
+            &self.strategy,
+            &self.vault,
+            &self.vault_authority.key(),
+            self.vault_base_token.amount,
+            nav_accounts,
+            Clock::get()?.unix_timestamp as u64,
+        )
+    }
     
-    // Remaining accounts will be passed as remaining accounts
+    // Share supply after accruing pending fees
+    fn total_shares(&self, total_assets: u64) -> Result<u64> {
+        fees::total_shares_after_accrual(
+            &self.vault,
+            total_assets,
+            self.vault_shares.supply,
+            Clock::get()?.unix_timestamp as u64,
+        )
+    }
 }
 
-// Deposit instruction
+// Vault view instructions for the owner of a position
 #[derive(Accounts)]
-pub struct Deposit<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    
-    #[account(mut)]
+pub struct OwnerView<'info> {
     pub vault: Account<'info, Vault>,
     
-    #[account(mut)]
+    #[account(
+        seeds = [STRATEGY_SEED, vault.key().as_ref()],
+        bump = strategy.bump
+    )]
+    pub strategy: Account<'info, Strategy>,
+    
+    #[account(address = vault.base_token_account)]
     pub vault_base_token: Account<'info, TokenAccount>,
     
-    #[account(mut)]
+    #[account(
+        seeds = ["shares".as_bytes(), vault.key().as_ref()],
+        bump
+    )]
     pub vault_shares: Account<'info, Mint>,
     
-    /// CHECK: PDA used in transaction
+    /// CHECK: PDA used to derive vault token accounts
     #[account(
         seeds = [VAULT_SEED, vault.base_token_mint.as_ref()],
         bump
     )]
     pub vault_authority: AccountInfo<'info>,
     
-    #[account(mut)]
-    pub user_token: Account<'info, TokenAccount>,
+    #[account(
+        seeds = [POSITION_SEED, vault.key().as_ref(), position.owner.as_ref()],
+        bump = position.bump
+    )]
+    pub position: Account<'info, InvestorPosition>,
     
-    #[account(mut)]
-    pub user_shares: Account<'info, TokenAccount>,
+    #[account(
+        associated_token::mint = vault_shares,
+        associated_token::authority = position.owner
+    )]
+    pub owner_shares: Account<'info, TokenAccount>,
     
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
+    // Remaining accounts are the NAV accounts, see nav::total_assets
 }
 
-// Withdraw instruction
+impl OwnerView<'_> {
+    fn total_assets(&self, nav_accounts: &[AccountInfo]) -> Result<u64> {
+        nav::total_assets(
+            &self.strategy,
+            &self.vault,
+            &self.vault_authority.key(),
+            self.vault_base_token.amount,
+            nav_accounts,
+            Clock::get()?.unix_timestamp as u64,
+        )
+    }
+    
+    // Share supply after accruing pending fees
+    fn total_shares(&self, total_assets: u64) -> Result<u64> {
+        fees::total_shares_after_accrual(
+            &self.vault,
+            total_assets,
+            self.vault_shares.supply,
+            Clock::get()?.unix_timestamp as u64,
+        )
+    }
+    
+    // Shares the position can redeem now, including fee shares it has not
+    // collected yet, see Vault::max_redeem
+    fn max_redeem(&self, total_assets: u64, total_shares: u64) -> Result<u64> {
+        let mut position = (*self.position).clone();
+        position.collect_fee_shares(self.owner_shares.amount);
+        // Fees accrued since the last accrual go to the current fee
+        // recipient, like total_shares
+        let accrued_shares = if self.vault.fee_recipient == position.owner {
+            total_shares
+                .checked_sub(self.vault_shares.supply)
+                .ok_or(MyVaultError::MathOverflow)?
+        } else {
+            0
+        };
+        self.vault.max_redeem(
+            &position,
+            position.shares.checked_add(accrued_shares).ok_or(MyVaultError::MathOverflow)?,
+            self.vault_base_token.amount,
+            total_assets,
+            total_shares,
+            Clock::get()?.unix_timestamp as u64,
+        )
+    }
+}
+
+// Update investor instruction
 #[derive(Accounts)]
-pub struct Withdraw<'info> {
+pub struct UpdateInvestor<'info> {
     #[account(mut)]
     pub authority: Signer<'info>,
     
     #[account(mut)]
     pub vault: Account<'info, Vault>,
     
+    pub system_program: Program<'info, System>,
+}
+
+// Add allowed investor instruction
+#[derive(Accounts)]
+#[instruction(investor: Pubkey)]
+pub struct AddAllowedInvestor<'info> {
     #[account(mut)]
-    pub vault_base_token: Account<'info, TokenAccount>,
+    pub authority: Signer<'info>,
     
-    #[account(mut)]
-    pub vault_shares: Account<'info, Mint>,
+    pub vault: Account<'info, Vault>,
     
-    /// CHECK: PDA used in transaction
     #[account(
-        seeds = [VAULT_SEED, vault.base_token_mint.as_ref()],
+        init_if_needed,
+        payer = authority,
+        space = 8 + 32 + 32 + 1 + 8 + 8 + 8 + 8 + 8 + 1,
+        seeds = [POSITION_SEED, vault.key().as_ref(), investor.as_ref()],
         bump
     )]
-    pub vault_authority: AccountInfo<'info>,
+    pub position: Account<'info, InvestorPosition>,
     
-    #[account(mut)]
-    pub user_token: Account<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+}
+
+// Remove allowed investor instruction
+#[derive(Accounts)]
+#[instruction(investor: Pubkey)]
+pub struct RemoveAllowedInvestor<'info> {
+    pub authority: Signer<'info>,
     
-    #[account(mut)]
-    pub user_shares: Account<'info, TokenAccount>,
+    pub vault: Account<'info, Vault>,
     
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
+    #[account(
+        mut,
+        seeds = [POSITION_SEED, vault.key().as_ref(), investor.as_ref()],
+        bump = position.bump
+    )]
+    pub position: Account<'info, InvestorPosition>,
 }
 
-// Update investor instruction
+// Open position instruction
 #[derive(Accounts)]
-pub struct UpdateInvestor<'info> {
+pub struct OpenPosition<'info> {
     #[account(mut)]
     pub authority: Signer<'info>,
     
-    #[account(mut)]
     pub vault: Account<'info, Vault>,
     
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + 32 + 32 + 1 + 8 + 8 + 8 + 8 + 8 + 1,
+        seeds = [POSITION_SEED, vault.key().as_ref(), authority.key().as_ref()],
+        bump
+    )]
+    pub position: Account<'info, InvestorPosition>,
+    
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct Initialize {}
\ No newline at end of file
+pub struct Initialize {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strategy(signal_timeout: u64) -> Strategy {
+        Strategy {
+            signal_timeout,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn accepts_signals_within_timeout() {
+        let strategy = strategy(900);
+        assert!(strategy.check_signal_age(1_000, 1_000).is_ok());
+        assert!(strategy.check_signal_age(1_000, 1_900).is_ok());
+        assert_eq!(
+            strategy.check_signal_age(1_000, 1_901).unwrap_err(),
+            error!(MyVaultError::SignalExpired)
+        );
+    }
+
+    #[test]
+    fn bounds_future_timestamps_by_clock_skew() {
+        let strategy = strategy(900);
+        assert!(strategy.check_signal_age(1_000 + MAX_SIGNAL_CLOCK_SKEW, 1_000).is_ok());
+        assert_eq!(
+            strategy.check_signal_age(1_001 + MAX_SIGNAL_CLOCK_SKEW, 1_000).unwrap_err(),
+            error!(MyVaultError::InvalidSignalTimestamp)
+        );
+        // A long timeout does not widen the skew
+        assert_eq!(
+            strategy.check_signal_age(1_900, 1_000).unwrap_err(),
+            error!(MyVaultError::InvalidSignalTimestamp)
+        );
+    }
+
+    #[test]
+    fn accepts_nonces_out_of_order_within_window() {
+        let mut strategy = strategy(900);
+        strategy.consume_nonce(10).unwrap();
+        strategy.consume_nonce(8).unwrap();
+        strategy.consume_nonce(12).unwrap();
+        strategy.consume_nonce(9).unwrap();
+        assert_eq!(strategy.last_signal_nonce, 12);
+    }
+
+    #[test]
+    fn rejects_replayed_nonces() {
+        let mut strategy = strategy(900);
+        strategy.consume_nonce(10).unwrap();
+        strategy.consume_nonce(7).unwrap();
+        assert_eq!(strategy.consume_nonce(10).unwrap_err(), error!(MyVaultError::SignalReplayed));
+        assert_eq!(strategy.consume_nonce(7).unwrap_err(), error!(MyVaultError::SignalReplayed));
+
+        // Still detected after the window slid forward
+        strategy.consume_nonce(20).unwrap();
+        assert_eq!(strategy.consume_nonce(7).unwrap_err(), error!(MyVaultError::SignalReplayed));
+    }
+
+    #[test]
+    fn rejects_nonces_behind_the_window() {
+        let mut strategy = strategy(900);
+        strategy.consume_nonce(NONCE_WINDOW + 5).unwrap();
+        strategy.consume_nonce(6).unwrap();
+        assert_eq!(strategy.consume_nonce(5).unwrap_err(), error!(MyVaultError::SignalNonceTooOld));
+
+        // A jump past the window forgets every older nonce
+        strategy.consume_nonce(3 * NONCE_WINDOW).unwrap();
+        assert_eq!(strategy.signal_nonce_bitmap, 1);
+        assert_eq!(
+            strategy.consume_nonce(2 * NONCE_WINDOW).unwrap_err(),
+            error!(MyVaultError::SignalNonceTooOld)
+        );
+    }
+
+    #[test]
+    fn measures_swap_amounts() {
+        // Spent 1_000 of 5_000, received 480 on top of 20
+        assert_eq!(measure_swap(5_000, 4_000, 20, 500, 1_000, 480).unwrap(), (1_000, 480));
+        // Partial fills spend less than the signal amount
+        assert_eq!(measure_swap(5_000, 4_500, 20, 270, 1_000, 250).unwrap(), (500, 250));
+    }
+
+    #[test]
+    fn rejects_swaps_outside_signal_bounds() {
+        assert_eq!(
+            measure_swap(5_000, 3_999, 20, 500, 1_000, 480).unwrap_err(),
+            error!(MyVaultError::SwapInputExceedsAmount)
+        );
+        assert_eq!(
+            measure_swap(5_000, 4_000, 20, 499, 1_000, 480).unwrap_err(),
+            error!(MyVaultError::SlippageExceeded)
+        );
+        // Output account drained during the swap
+        assert_eq!(
+            measure_swap(5_000, 4_000, 20, 10, 1_000, 0).unwrap_err(),
+            error!(MyVaultError::SlippageExceeded)
+        );
+    }
+
+    #[test]
+    fn values_tokens_at_a_fresh_price_feed() {
+        let feed = PriceFeed {
+            token_mint: Pubkey::new_unique(),
+            base_mint: Pubkey::new_unique(),
+            // 2.5 base units per token unit
+            price: PRICE_SCALE * 5 / 2,
+            last_update_timestamp: 1_000,
+            bump: 0,
+        };
+        assert_eq!(feed.token_value_in_base(1_001, 1_060, 60).unwrap(), 2_502);
+        assert_eq!(
+            feed.token_value_in_base(1_001, 1_061, 60).unwrap_err(),
+            error!(MyVaultError::PriceStale)
+        );
+        assert_eq!(
+            feed.token_value_in_base(u64::MAX, 1_060, 60).unwrap_err(),
+            error!(MyVaultError::MathOverflow)
+        );
+
+        let never_updated = PriceFeed {
+            last_update_timestamp: 0,
+            ..feed
+        };
+        assert_eq!(
+            never_updated.token_value_in_base(1_001, 1_060, 60).unwrap_err(),
+            error!(MyVaultError::PriceNotAvailable)
+        );
+    }
+
+    #[test]
+    fn route_instruction_signs_as_vault_authority() {
+        let swap_program = Pubkey::new_unique();
+        let vault_authority = Pubkey::new_unique();
+        let keys = [vault_authority, Pubkey::new_unique(), Pubkey::new_unique()];
+        let owner = Pubkey::default();
+        let mut lamports = [0u64; 3];
+        let mut data = [[0u8; 0]; 3];
+        let accounts: Vec<AccountInfo> = keys
+            .iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .enumerate()
+            .map(|(i, ((key, lamports), data))| {
+                AccountInfo::new(key, i == 2, i == 1, lamports, data, &owner, false, 0)
+            })
+            .collect();
+
+        let instruction = swap_route_instruction(swap_program, vault_authority, &accounts, vec![1, 2, 3]);
+        assert_eq!(instruction.program_id, swap_program);
+        assert_eq!(instruction.data, vec![1, 2, 3]);
+
+        let flags: Vec<(Pubkey, bool, bool)> = instruction
+            .accounts
+            .iter()
+            .map(|meta| (meta.pubkey, meta.is_signer, meta.is_writable))
+            .collect();
+        assert_eq!(
+            flags,
+            vec![
+                (keys[0], true, false),
+                (keys[1], false, true),
+                (keys[2], true, false),
+            ]
+        );
+    }
+
+    fn position() -> InvestorPosition {
+        InvestorPosition {
+            vault: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            allowlisted: false,
+            deposited: 0,
+            withdrawn: 0,
+            shares: 0,
+            cost_basis: 0,
+            unlock_time: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn position_tracks_shares_and_cost() {
+        let mut position = position();
+        position.record_deposit(1_000, 800, 0, 0).unwrap();
+        position.record_deposit(500, 200, 0, 0).unwrap();
+        assert_eq!(position.shares, 1_000);
+        assert_eq!(position.cost_basis, 1_500);
+
+        // Cost is removed pro rata to the recorded shares
+        assert_eq!(position.remove_shares(400).unwrap(), 600);
+        assert_eq!(position.shares, 600);
+        assert_eq!(position.cost_basis, 900);
+
+        // A cancelled request returns its shares and cost
+        position.restore_shares(400, 600).unwrap();
+        assert_eq!(position.shares, 1_000);
+        assert_eq!(position.cost_basis, 1_500);
+
+        assert_eq!(position.remove_shares(1_000).unwrap(), 1_500);
+        assert_eq!(position.shares, 0);
+        assert_eq!(position.cost_basis, 0);
+    }
+
+    #[test]
+    fn position_averages_unlock_time_over_shares() {
+        let mut position = position();
+        position.record_deposit(1_000, 1_000, 2_000, 1_000).unwrap();
+        assert_eq!(position.unlock_time, 2_000);
+
+        // Half the shares unlock at 2,000 and half at 4,000
+        position.record_deposit(1_000, 1_000, 4_000, 1_000).unwrap();
+        assert_eq!(position.unlock_time, 3_000);
+    }
+
+    #[test]
+    fn max_redeem_respects_lockup_liquidity_and_min_balance() {
+        let mut vault = Vault::default();
+        let mut position = position();
+        position.unlock_time = 2_000;
+
+        // Locked without an early exit fee
+        assert_eq!(vault.max_redeem(&position, 500, 1_000, 1_000, 1_000, 1_000).unwrap(), 0);
+        vault.early_exit_fee_bps = 100;
+        assert_eq!(vault.max_redeem(&position, 500, 1_000, 1_000, 1_000, 1_000).unwrap(), 500);
+
+        // The base balance covers a full exit
+        position.unlock_time = 0;
+        vault.min_balance = 250;
+        assert_eq!(vault.max_redeem(&position, 500, 1_000, 1_000, 1_000, 1_000).unwrap(), 500);
+
+        // Otherwise the liquid shares, leaving at least the minimum balance
+        assert_eq!(vault.max_redeem(&position, 500, 300, 1_000, 1_000, 1_000).unwrap(), 250);
+        vault.min_balance = 0;
+        assert_eq!(vault.max_redeem(&position, 500, 300, 1_000, 1_000, 1_000).unwrap(), 300);
+    }
+
+    #[test]
+    fn fee_shares_stay_with_the_recipient_they_were_minted_to() {
+        // The old recipient deposited for 1_000 shares and was minted 100
+        // fee shares before the recipient changed, the new one 50 after
+        let mut old_recipient = position();
+        old_recipient.record_deposit(1_000, 1_000, 0, 0).unwrap();
+        let mut new_recipient = position();
+
+        old_recipient.collect_fee_shares(1_100);
+        new_recipient.collect_fee_shares(50);
+        assert_eq!((old_recipient.shares, old_recipient.cost_basis), (1_100, 1_000));
+        assert_eq!((new_recipient.shares, new_recipient.cost_basis), (50, 0));
+
+        // Collected shares are not collected twice, and escrowed shares are
+        // not collected back
+        old_recipient.collect_fee_shares(1_100);
+        old_recipient.remove_shares(600).unwrap();
+        old_recipient.collect_fee_shares(500);
+        assert_eq!(old_recipient.shares, 500);
+    }
+
+    #[test]
+    fn position_removes_at_most_its_shares() {
+        let mut position = position();
+        position.record_deposit(1_000, 1_000, 0, 0).unwrap();
+        assert_eq!(position.remove_shares(u64::MAX).unwrap(), 1_000);
+        assert_eq!((position.shares, position.cost_basis), (0, 0));
+        assert_eq!(position.remove_shares(1).unwrap(), 0);
+    }
+
+    #[test]
+    fn exit_amount_is_bounded_by_the_balance() {
+        let pair = TradingPair {
+            token_mint: Pubkey::new_unique(),
+            is_active: true,
+            max_allocation: 0,
+            min_exit_amount: 100,
+            venue: SwapVenue::Jupiter,
+            price_source: PriceSource::PriceFeed,
+            token_decimals: 9,
+            max_oracle_deviation_bps: 0,
+        };
+        assert_eq!(pair.exit_amount(1_000, 1_000).unwrap(), 1_000);
+        assert_eq!(pair.exit_amount(900, 1_000).unwrap(), 900);
+        // Leaving less than the minimum exit amount sells everything
+        assert_eq!(pair.exit_amount(901, 1_000).unwrap(), 1_000);
+        assert_eq!(pair.exit_amount(99, 1_000).unwrap_err(), error!(MyVaultError::SellBelowMinExit));
+        assert_eq!(
+            pair.exit_amount(1_001, 1_000).unwrap_err(),
+            error!(MyVaultError::InsufficientVaultBalance)
+        );
+    }
+
+    #[test]
+    fn last_withdraw_claim_takes_the_remainder() {
+        let mut epoch = WithdrawEpoch {
+            vault: Pubkey::new_unique(),
+            epoch: 0,
+            shares: 3,
+            assets: 100,
+            processed_at: 0,
+            bump: 0,
+        };
+        assert_eq!(epoch.claim(1).unwrap(), 33);
+        assert_eq!(epoch.claim(1).unwrap(), 33);
+        assert_eq!(epoch.claim(1).unwrap(), 34);
+        assert_eq!((epoch.shares, epoch.assets), (0, 0));
+
+        // More shares than are left in the epoch
+        epoch.shares = 1;
+        epoch.assets = 10;
+        assert_eq!(epoch.claim(2).unwrap_err(), error!(MyVaultError::MathOverflow));
+    }
+
+    #[test]
+    fn early_exit_fee_never_exceeds_the_assets() {
+        let vault = Vault {
+            early_exit_fee_bps: MAX_EARLY_EXIT_FEE_BPS,
+            ..Default::default()
+        };
+        let mut position = position();
+        position.unlock_time = 2_000;
+        assert_eq!(vault.assets_after_early_exit_fee(&position, 1_000, 1_000).unwrap(), 900);
+        assert_eq!(vault.assets_after_early_exit_fee(&position, 1, 1_000).unwrap(), 0);
+        assert_eq!(
+            vault.assets_after_early_exit_fee(&position, u64::MAX, 1_000).unwrap(),
+            u64::MAX - u64::MAX.div_ceil(10)
+        );
+        // No fee once unlocked
+        assert_eq!(vault.assets_after_early_exit_fee(&position, 1_000, 2_000).unwrap(), 1_000);
+    }
+}
@@ -0,0 +1,100 @@
+use anchor_lang::prelude::*;
+
+use crate::{MyVaultError, BASIS_POINTS};
+
+// Rounding of a division. Vault calculations round in favor of the vault:
+// down when paying out, up when charging.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Rounding {
+    Down,
+    Up,
+}
+
+// a * b / c with the given rounding. Computed as a * (b / c) + a * (b % c) / c,
+// which is exact and only overflows when the result or a * (b % c) does not
+// fit in u128.
+pub fn mul_div(a: u128, b: u128, c: u128, rounding: Rounding) -> Result<u128> {
+    require!(c > 0, MyVaultError::DivisionByZero);
+
+    let whole = a.checked_mul(b / c).ok_or(MyVaultError::MathOverflow)?;
+    let remainder = a.checked_mul(b % c).ok_or(MyVaultError::MathOverflow)?;
+    let fraction = match rounding {
+        Rounding::Down => remainder / c,
+        Rounding::Up => remainder.div_ceil(c),
+    };
+    whole
+        .checked_add(fraction)
+        .ok_or_else(|| error!(MyVaultError::MathOverflow))
+}
+
+pub fn mul_div_floor(a: u128, b: u128, c: u128) -> Result<u128> {
+    mul_div(a, b, c, Rounding::Down)
+}
+
+pub fn mul_div_ceil(a: u128, b: u128, c: u128) -> Result<u128> {
+    mul_div(a, b, c, Rounding::Up)
+}
+
+// a * b / c on token amounts
+pub fn mul_div_u64(a: u64, b: u64, c: u64, rounding: Rounding) -> Result<u64> {
+    to_u64(mul_div(a as u128, b as u128, c as u128, rounding)?)
+}
+
+// Basis-point share of an amount
+pub fn bps(amount: u64, basis_points: u64, rounding: Rounding) -> Result<u64> {
+    mul_div_u64(amount, basis_points, BASIS_POINTS, rounding)
+}
+
+pub fn to_u64(value: u128) -> Result<u64> {
+    u64::try_from(value).map_err(|_| error!(MyVaultError::MathOverflow))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rounds_down_and_up() {
+        assert_eq!(mul_div_floor(10, 10, 3).unwrap(), 33);
+        assert_eq!(mul_div_ceil(10, 10, 3).unwrap(), 34);
+        assert_eq!(mul_div_floor(10, 9, 3).unwrap(), 30);
+        assert_eq!(mul_div_ceil(10, 9, 3).unwrap(), 30);
+    }
+
+    #[test]
+    fn zero_operands() {
+        assert_eq!(mul_div_floor(0, u128::MAX, 7).unwrap(), 0);
+        assert_eq!(mul_div_ceil(u128::MAX, 0, 7).unwrap(), 0);
+    }
+
+    #[test]
+    fn rejects_division_by_zero() {
+        assert_eq!(mul_div_floor(1, 1, 0).unwrap_err(), error!(MyVaultError::DivisionByZero));
+        assert_eq!(mul_div_u64(0, 0, 0, Rounding::Up).unwrap_err(), error!(MyVaultError::DivisionByZero));
+    }
+
+    #[test]
+    fn exact_when_product_exceeds_u128() {
+        // a * b overflows u128 but the result fits
+        let a = u128::MAX / 3;
+        assert_eq!(mul_div_floor(a, 6, 6).unwrap(), a);
+        assert_eq!(mul_div_floor(a, 1_000_000_000_000_000_000, 1_000_000_000_000_000_000).unwrap(), a);
+        assert_eq!(mul_div_ceil(a, 7, 7).unwrap(), a);
+    }
+
+    #[test]
+    fn rejects_overflowing_results() {
+        assert_eq!(mul_div_floor(u128::MAX, 2, 1).unwrap_err(), error!(MyVaultError::MathOverflow));
+        assert_eq!(mul_div_ceil(u128::MAX, 1, 1).unwrap(), u128::MAX);
+        assert_eq!(mul_div_u64(u64::MAX, 2, 1, Rounding::Down).unwrap_err(), error!(MyVaultError::MathOverflow));
+        assert_eq!(mul_div_u64(u64::MAX, u64::MAX, u64::MAX, Rounding::Up).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn basis_points() {
+        assert_eq!(bps(1_000, 2_500, Rounding::Down).unwrap(), 250);
+        assert_eq!(bps(999, 1, Rounding::Down).unwrap(), 0);
+        assert_eq!(bps(999, 1, Rounding::Up).unwrap(), 1);
+        assert_eq!(bps(u64::MAX, BASIS_POINTS, Rounding::Down).unwrap(), u64::MAX);
+    }
+}
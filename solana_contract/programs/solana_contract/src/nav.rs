@@ -0,0 +1,208 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, TokenAccount};
+
+use crate::math::{mul_div_u64, Rounding};
+use crate::{check_vault_token_account, MyVaultError, Strategy, TradingPair, Vault};
+
+// Virtual shares and assets added to the vault totals in share conversions,
+// like OpenZeppelin ERC4626. A donation to the vault is shared with the
+// virtual shares, so inflating the share price before other deposits always
+// costs the donor more than it takes from them.
+pub(crate) const VIRTUAL_SHARES: u64 = 1;
+pub(crate) const VIRTUAL_ASSETS: u64 = 1;
+
+// Shares worth `assets` at NAV, 1:1 for an empty vault
+pub fn convert_to_shares(assets: u64, total_assets: u64, total_shares: u64, rounding: Rounding) -> Result<u64> {
+    mul_div_u64(
+        assets,
+        total_shares.checked_add(VIRTUAL_SHARES).ok_or(MyVaultError::MathOverflow)?,
+        total_assets.checked_add(VIRTUAL_ASSETS).ok_or(MyVaultError::MathOverflow)?,
+        rounding,
+    )
+}
+
+// Assets worth `shares` at NAV, 1:1 for an empty vault
+pub fn convert_to_assets(shares: u64, total_assets: u64, total_shares: u64, rounding: Rounding) -> Result<u64> {
+    mul_div_u64(
+        shares,
+        total_assets.checked_add(VIRTUAL_ASSETS).ok_or(MyVaultError::MathOverflow)?,
+        total_shares.checked_add(VIRTUAL_SHARES).ok_or(MyVaultError::MathOverflow)?,
+        rounding,
+    )
+}
+
+// A non-base trading pair holding, valued at its price source
+pub struct Position<'a, 'info> {
+    pub pair: TradingPair,
+    pub token_account: &'a AccountInfo<'info>,
+    pub price_source: &'a AccountInfo<'info>,
+    pub base_price_source: Option<&'a AccountInfo<'info>>,
+    pub balance: u64,
+    pub value: u64,     // Value in base token units, the price is not read when the balance is zero
+}
+
+// Number of NAV accounts for the strategy, see positions
+pub fn nav_accounts_len(strategy: &Strategy, vault: &Vault) -> usize {
+    let base_price_accounts = strategy.base_pyth_feed_id.map_or(0, |_| 1);
+    let pairs = strategy.trading_pairs
+        .iter()
+        .filter(|p| p.token_mint != vault.base_token_mint)
+        .count();
+    base_price_accounts + 2 * pairs
+}
+
+// Value every non-base trading pair holding. Disabled pairs are included, as
+// the vault may still hold their tokens; their price is only read when it does.
+//
+// `accounts` are the NAV accounts, passed as remaining accounts: the base token
+// Pyth price account first if the strategy has a base Pyth feed, then for each
+// non-base trading pair in strategy order its vault associated token account
+// and its price source.
+pub fn positions<'a, 'info>(
+    strategy: &Strategy,
+    vault: &Vault,
+    vault_authority: &Pubkey,
+    accounts: &'a [AccountInfo<'info>],
+    now: u64,
+) -> Result<Vec<Position<'a, 'info>>> {
+    require!(
+        accounts.len() == nav_accounts_len(strategy, vault),
+        MyVaultError::InvalidNavAccounts
+    );
+    let (base_price_source, mut accounts) = match strategy.base_pyth_feed_id {
+        Some(_) => {
+            let (first, rest) = accounts
+                .split_first()
+                .ok_or(MyVaultError::InvalidNavAccounts)?;
+            (Some(first), rest)
+        }
+        None => (None, accounts),
+    };
+
+    let mut positions = Vec::new();
+    for pair in strategy.trading_pairs
+        .iter()
+        .filter(|p| p.token_mint != vault.base_token_mint)
+    {
+        let [token_account, price_source, rest @ ..] = accounts else {
+            return err!(MyVaultError::InvalidNavAccounts);
+        };
+        accounts = rest;
+
+        check_vault_token_account(vault, vault_authority, &pair.token_mint, token_account.key)?;
+        let balance = token_balance(token_account)?;
+        let value = if balance == 0 {
+            0
+        } else {
+            strategy.pair_value(vault, pair, balance, Some(price_source), base_price_source, now)?
+        };
+
+        positions.push(Position {
+            pair: *pair,
+            token_account,
+            price_source,
+            base_price_source,
+            balance,
+            value,
+        });
+    }
+
+    Ok(positions)
+}
+
+// Total vault assets in base token units, like totalAssets() on EVM: the base
+// balance not reserved for processed withdrawals plus every trading pair
+// balance valued at its price source. `accounts` are the NAV accounts,
+// see positions.
+pub fn total_assets(
+    strategy: &Strategy,
+    vault: &Vault,
+    vault_authority: &Pubkey,
+    base_balance: u64,
+    accounts: &[AccountInfo],
+    now: u64,
+) -> Result<u64> {
+    positions(strategy, vault, vault_authority, accounts, now)?
+        .iter()
+        .try_fold(vault.available_base(base_balance), |total, position| total.checked_add(position.value))
+        .ok_or_else(|| error!(MyVaultError::MathOverflow))
+}
+
+// Balance of a vault associated token account, zero if it was never created
+pub fn token_balance(account: &AccountInfo) -> Result<u64> {
+    if account.data_is_empty() {
+        return Ok(0);
+    }
+
+    require_keys_eq!(*account.owner, token::ID, MyVaultError::InvalidVaultTokenAccount);
+    let token_account = TokenAccount::try_deserialize(&mut &account.try_borrow_data()?[..])?;
+    Ok(token_account.amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_vault_converts_one_to_one() {
+        assert_eq!(convert_to_shares(1_000, 0, 0, Rounding::Down).unwrap(), 1_000);
+        assert_eq!(convert_to_assets(1_000, 0, 0, Rounding::Down).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn rounding_favors_the_vault() {
+        // 3 shares backed by 10 assets
+        assert_eq!(convert_to_shares(5, 10, 3, Rounding::Down).unwrap(), 1);
+        assert_eq!(convert_to_shares(5, 10, 3, Rounding::Up).unwrap(), 2);
+        assert_eq!(convert_to_assets(1, 10, 3, Rounding::Down).unwrap(), 2);
+        assert_eq!(convert_to_assets(1, 10, 3, Rounding::Up).unwrap(), 3);
+    }
+
+    #[test]
+    fn donation_attack_is_unprofitable() {
+        // Attacker deposits 1 asset for 1 share, then donates 1e9 to the vault
+        let attacker_shares = convert_to_shares(1, 0, 0, Rounding::Down).unwrap();
+        let donation = 1_000_000_000;
+        let total_assets = 1 + donation;
+
+        // Victim deposit still mints shares instead of rounding to zero
+        let deposit = 1_000_000_000;
+        let victim_shares = convert_to_shares(deposit, total_assets, attacker_shares, Rounding::Down).unwrap();
+        assert!(victim_shares > 0);
+
+        // Attacker cannot redeem more than they put in
+        let total_assets = total_assets + deposit;
+        let total_shares = attacker_shares + victim_shares;
+        let attacker_assets = convert_to_assets(attacker_shares, total_assets, total_shares, Rounding::Down).unwrap();
+        assert!(attacker_assets < 1 + donation);
+    }
+
+    #[test]
+    fn counts_disabled_pairs() {
+        let vault = Vault {
+            base_token_mint: Pubkey::new_unique(),
+            ..Default::default()
+        };
+        let pair = |token_mint: Pubkey, is_active: bool| TradingPair {
+            token_mint,
+            is_active,
+            max_allocation: 0,
+            min_exit_amount: 0,
+            venue: crate::SwapVenue::Jupiter,
+            price_source: crate::PriceSource::PriceFeed,
+            token_decimals: 9,
+            max_oracle_deviation_bps: 0,
+        };
+        let strategy = Strategy {
+            trading_pairs: vec![
+                pair(Pubkey::new_unique(), true),
+                pair(Pubkey::new_unique(), false),
+                pair(vault.base_token_mint, true),
+            ],
+            ..Default::default()
+        };
+
+        // Token account and price source of both non-base pairs
+        assert_eq!(nav_accounts_len(&strategy, &vault), 4);
+    }
+}
@@ -0,0 +1,235 @@
+use anchor_lang::prelude::*;
+
+use crate::{MyVaultError, BASIS_POINTS, PRICE_SCALE};
+
+// Pyth Solana receiver program, owner of PriceUpdateV2 accounts
+pub const PYTH_RECEIVER_ID: Pubkey = pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+
+// PriceUpdateV2 layout: [discriminator: 8, write_authority: 32,
+// verification_level: 1 (+1 num_signatures if partial), price message...]
+const PRICE_UPDATE_V2_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+const VERIFICATION_LEVEL_OFFSET: usize = 40;
+const VERIFICATION_LEVEL_FULL: u8 = 1;
+
+// Price message of a fully verified update: [feed_id: 32, price: i64,
+// conf: u64, exponent: i32, publish_time: i64, ...]
+const MESSAGE_OFFSET: usize = 41;
+const MESSAGE_SIZE: usize = 32 + 8 + 8 + 4 + 8;
+
+// Price of one whole token, price * 10^exponent, +/- conf * 10^exponent
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PythPrice {
+    pub feed_id: [u8; 32],
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+}
+
+// Decode a PriceUpdateV2 account. Only updates fully verified by the
+// Wormhole guardian set are accepted.
+pub fn parse_price_update(data: &[u8]) -> Result<PythPrice> {
+    require!(
+        data.len() >= MESSAGE_OFFSET + MESSAGE_SIZE && data[..8] == PRICE_UPDATE_V2_DISCRIMINATOR,
+        MyVaultError::InvalidPythAccount
+    );
+    require!(
+        data[VERIFICATION_LEVEL_OFFSET] == VERIFICATION_LEVEL_FULL,
+        MyVaultError::PythPriceUnverified
+    );
+
+    let message = &data[MESSAGE_OFFSET..MESSAGE_OFFSET + MESSAGE_SIZE];
+    let mut feed_id = [0u8; 32];
+    feed_id.copy_from_slice(&message[..32]);
+    Ok(PythPrice {
+        feed_id,
+        price: i64::from_le_bytes(message[32..40].try_into().unwrap()),
+        conf: u64::from_le_bytes(message[40..48].try_into().unwrap()),
+        exponent: i32::from_le_bytes(message[48..52].try_into().unwrap()),
+        publish_time: i64::from_le_bytes(message[52..60].try_into().unwrap()),
+    })
+}
+
+// Reject prices of another feed, non-positive, published more than max_age
+// seconds ago or with a confidence interval wider than max_confidence_bps
+pub fn check_price(
+    price: &PythPrice,
+    feed_id: &[u8; 32],
+    now: i64,
+    max_age: u64,
+    max_confidence_bps: u64,
+) -> Result<()> {
+    require!(price.feed_id == *feed_id, MyVaultError::InvalidPythAccount);
+    require!(price.price > 0, MyVaultError::InvalidPrice);
+    require!(
+        now.saturating_sub(price.publish_time) <= max_age as i64,
+        MyVaultError::PriceStale
+    );
+    require!(
+        price.conf as u128 * BASIS_POINTS as u128 <= price.price as u128 * max_confidence_bps as u128,
+        MyVaultError::PythConfidenceTooWide
+    );
+    Ok(())
+}
+
+// Load and check the price in a PriceUpdateV2 account
+pub fn load_price(
+    account: &AccountInfo,
+    feed_id: &[u8; 32],
+    now: i64,
+    max_age: u64,
+    max_confidence_bps: u64,
+) -> Result<PythPrice> {
+    require_keys_eq!(*account.owner, PYTH_RECEIVER_ID, MyVaultError::InvalidPythAccount);
+
+    let price = parse_price_update(&account.try_borrow_data()?)?;
+    check_price(&price, feed_id, now, max_age, max_confidence_bps)?;
+    Ok(price)
+}
+
+// Convert a Pyth token price into base token units per token unit, scaled by
+// PRICE_SCALE like PriceFeed prices. Both prices must be in the same quote
+// currency; without a base price the token price is read as quoted in whole
+// base tokens.
+pub fn price_in_base(
+    token: &PythPrice,
+    token_decimals: u8,
+    base: Option<&PythPrice>,
+    base_decimals: u8,
+) -> Result<u128> {
+    let (base_price, base_exponent) = base.map_or((1, 0), |b| (b.price, b.exponent));
+    require!(token.price > 0 && base_price > 0, MyVaultError::InvalidPrice);
+
+    // token.price * 10^(token.exponent - token_decimals) / (base_price * 10^(base_exponent - base_decimals))
+    let exponent = token.exponent as i64 - token_decimals as i64 - base_exponent as i64
+        + base_decimals as i64
+        + PRICE_SCALE.ilog10() as i64;
+    let power = 10u128
+        .checked_pow(exponent.unsigned_abs() as u32)
+        .ok_or(MyVaultError::MathOverflow)?;
+
+    let (numerator, denominator) = if exponent >= 0 {
+        (
+            (token.price as u128).checked_mul(power).ok_or(MyVaultError::MathOverflow)?,
+            base_price as u128,
+        )
+    } else {
+        (
+            token.price as u128,
+            (base_price as u128).checked_mul(power).ok_or(MyVaultError::MathOverflow)?,
+        )
+    };
+    Ok(numerator / denominator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOL_USD: [u8; 32] = [1; 32];
+    const USDC_USD: [u8; 32] = [2; 32];
+
+    // Fabricate PriceUpdateV2 account data the way the Pyth receiver writes it
+    fn price_update(feed_id: [u8; 32], price: i64, conf: u64, exponent: i32, publish_time: i64, full: bool) -> Vec<u8> {
+        let mut data = PRICE_UPDATE_V2_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&[7; 32]);
+        if full {
+            data.push(VERIFICATION_LEVEL_FULL);
+        } else {
+            data.extend_from_slice(&[0, 3]);
+        }
+        data.extend_from_slice(&feed_id);
+        data.extend_from_slice(&price.to_le_bytes());
+        data.extend_from_slice(&conf.to_le_bytes());
+        data.extend_from_slice(&exponent.to_le_bytes());
+        data.extend_from_slice(&publish_time.to_le_bytes());
+        data.extend_from_slice(&(publish_time - 1).to_le_bytes());
+        data.extend_from_slice(&price.to_le_bytes());
+        data.extend_from_slice(&conf.to_le_bytes());
+        data.extend_from_slice(&100u64.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn parses_fully_verified_update() {
+        let data = price_update(SOL_USD, 15_000_000_000, 5_000_000, -8, 1_700_000_000, true);
+        let price = parse_price_update(&data).unwrap();
+        assert_eq!(
+            price,
+            PythPrice {
+                feed_id: SOL_USD,
+                price: 15_000_000_000,
+                conf: 5_000_000,
+                exponent: -8,
+                publish_time: 1_700_000_000,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_partially_verified_update() {
+        let data = price_update(SOL_USD, 15_000_000_000, 5_000_000, -8, 1_700_000_000, false);
+        assert_eq!(
+            parse_price_update(&data).unwrap_err(),
+            error!(MyVaultError::PythPriceUnverified)
+        );
+    }
+
+    #[test]
+    fn rejects_other_accounts() {
+        let mut data = price_update(SOL_USD, 15_000_000_000, 5_000_000, -8, 1_700_000_000, true);
+        data[0] ^= 1;
+        assert_eq!(
+            parse_price_update(&data).unwrap_err(),
+            error!(MyVaultError::InvalidPythAccount)
+        );
+        assert_eq!(
+            parse_price_update(&data[..40]).unwrap_err(),
+            error!(MyVaultError::InvalidPythAccount)
+        );
+    }
+
+    #[test]
+    fn checks_feed_age_and_confidence() {
+        let data = price_update(SOL_USD, 15_000_000_000, 150_000_000, -8, 1_700_000_000, true);
+        let price = parse_price_update(&data).unwrap();
+
+        // 1% confidence interval, 60 seconds old
+        assert!(check_price(&price, &SOL_USD, 1_700_000_060, 60, 100).is_ok());
+        assert_eq!(
+            check_price(&price, &USDC_USD, 1_700_000_060, 60, 100).unwrap_err(),
+            error!(MyVaultError::InvalidPythAccount)
+        );
+        assert_eq!(
+            check_price(&price, &SOL_USD, 1_700_000_061, 60, 100).unwrap_err(),
+            error!(MyVaultError::PriceStale)
+        );
+        assert_eq!(
+            check_price(&price, &SOL_USD, 1_700_000_060, 60, 99).unwrap_err(),
+            error!(MyVaultError::PythConfidenceTooWide)
+        );
+    }
+
+    #[test]
+    fn rejects_non_positive_price() {
+        let data = price_update(SOL_USD, 0, 0, -8, 1_700_000_000, true);
+        let price = parse_price_update(&data).unwrap();
+        assert_eq!(
+            check_price(&price, &SOL_USD, 1_700_000_000, 60, 100).unwrap_err(),
+            error!(MyVaultError::InvalidPrice)
+        );
+    }
+
+    #[test]
+    fn converts_to_base_units() {
+        let sol = parse_price_update(&price_update(SOL_USD, 15_000_000_000, 0, -8, 0, true)).unwrap();
+        let usdc = parse_price_update(&price_update(USDC_USD, 100_000_000, 0, -8, 0, true)).unwrap();
+
+        // 150 USDC per SOL is 0.15 USDC units (6 decimals) per lamport (9 decimals)
+        assert_eq!(price_in_base(&sol, 9, Some(&usdc), 6).unwrap(), 150_000_000_000_000_000);
+        assert_eq!(price_in_base(&sol, 9, None, 6).unwrap(), 150_000_000_000_000_000);
+
+        // 1 USDC is 1/150 SOL, in lamports per USDC unit
+        assert_eq!(price_in_base(&usdc, 6, Some(&sol), 9).unwrap(), 6_666_666_666_666_666_666);
+    }
+}
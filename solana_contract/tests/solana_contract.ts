@@ -1,8 +1,9 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { SolanaContract } from "../target/types/solana_contract";
-import { PublicKey, Keypair, SystemProgram } from "@solana/web3.js";
-import { TOKEN_PROGRAM_ID, createMint, createAccount, getAssociatedTokenAddress, ASSOCIATED_TOKEN_PROGRAM_ID } from "@solana/spl-token";
+import { MockDex } from "../target/types/mock_dex";
+import { PublicKey, Keypair, SystemProgram, LAMPORTS_PER_SOL, SYSVAR_INSTRUCTIONS_PUBKEY } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID, createMint, createAccount, getAssociatedTokenAddress, ASSOCIATED_TOKEN_PROGRAM_ID, mintTo, getAccount, getOrCreateAssociatedTokenAccount, transfer } from "@solana/spl-token";
 
 describe("Solana Contract Test Suite", () => {
   console.log("=== Starting Test Suite ===");
@@ -13,6 +14,9 @@ describe("Solana Contract Test Suite", () => {
 
   const program = anchor.workspace.SolanaContract as Program<SolanaContract>;
   console.log("Program ID:", program.programId.toString());
+
+  const mockDex = anchor.workspace.MockDex as Program<MockDex>;
+  console.log("Mock DEX program ID:", mockDex.programId.toString());
   
   // Test accounts
   const authority = Keypair.generate();
@@ -24,14 +28,124 @@ describe("Solana Contract Test Suite", () => {
   let baseTokenMint: PublicKey;
   let vaultBaseToken: PublicKey;
   let vaultShares: PublicKey;
+  let withdrawEscrow: PublicKey;
   let vault: PublicKey;
   let strategy: PublicKey;
   let vaultAuthority: PublicKey;
   let userTokenAccount: PublicKey;
   let userSharesAccount: PublicKey;
+  let feeRecipientShares: PublicKey;
+  let investorPosition: PublicKey;
+
+  // Mock DEX trading accounts
+  let tradeTokenMint: PublicKey;
+  let vaultTradeToken: PublicKey;
+  let pool: PublicKey;
+  let poolBaseVault: PublicKey;
+  let poolTradeVault: PublicKey;
+  let signalNonce = 1;
+  const poolFeeBps = 30;
+
+  // Constant-product quote matching the mock DEX
+  const quote = async (amountIn: bigint, poolSource: PublicKey, poolDestination: PublicKey) => {
+    const reserveIn = (await getAccount(provider.connection, poolSource)).amount;
+    const reserveOut = (await getAccount(provider.connection, poolDestination)).amount;
+    const inAfterFee = (amountIn * BigInt(10000 - poolFeeBps)) / BigInt(10000);
+    return (reserveOut * inAfterFee) / (reserveIn + inAfterFee);
+  };
+
+  // Jupiter-compatible route instruction data and accounts for the mock DEX
+  const mockRoute = (
+    inAmount: bigint,
+    quotedOutAmount: bigint,
+    source: PublicKey,
+    destination: PublicKey,
+    destinationMint: PublicKey,
+    poolSource: PublicKey,
+    poolDestination: PublicKey
+  ) => {
+    const data = mockDex.coder.instruction.encode("route", {
+      routePlan: [],
+      inAmount: new anchor.BN(inAmount.toString()),
+      quotedOutAmount: new anchor.BN(quotedOutAmount.toString()),
+      slippageBps: 0,
+      platformFeeBps: 0,
+    });
+    const account = (pubkey: PublicKey, isWritable = false) => ({ pubkey, isSigner: false, isWritable });
+    const remainingAccounts = [
+      account(TOKEN_PROGRAM_ID),
+      account(vaultAuthority),
+      account(source, true),
+      account(destination, true),
+      account(mockDex.programId), // No separate destination account
+      account(destinationMint),
+      account(mockDex.programId), // No platform fee account
+      account(mockDex.programId), // Event authority, unused by the mock
+      account(mockDex.programId),
+      account(pool),
+      account(poolSource, true),
+      account(poolDestination, true),
+    ];
+    return { data, remainingAccounts };
+  };
+
+  // Price feed of the trade token in the base token
+  const tradePriceFeed = () =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("price_feed"), tradeTokenMint.toBuffer(), baseTokenMint.toBuffer()],
+      program.programId
+    )[0];
+
+  // NAV accounts: the vault trade token account and its price feed
+  const navAccounts = (isWritable = false) => [
+    { pubkey: vaultTradeToken, isSigner: false, isWritable },
+    { pubkey: tradePriceFeed(), isSigner: false, isWritable: false },
+  ];
+
+  const viewAccounts = () => ({ vault, strategy, vaultBaseToken, vaultShares, vaultAuthority });
+
+  // Deposit and withdraw accounts of the investor
+  const investorAccounts = () => ({
+    authority: investor.publicKey,
+    vault,
+    strategy,
+    vaultBaseToken,
+    vaultShares,
+    vaultAuthority,
+    userToken: userTokenAccount,
+    userShares: userSharesAccount,
+    position: investorPosition,
+    feeRecipientShares,
+    tokenProgram: TOKEN_PROGRAM_ID,
+    systemProgram: SystemProgram.programId,
+  });
+
+  // Run a call that must fail with the given program error
+  const expectError = async (label: string, code: string, call: () => Promise<unknown>) => {
+    console.log(`Calling ${label}...`);
+    let error: unknown;
+    try {
+      await call();
+    } catch (e) {
+      error = e;
+    }
+    if (!(error instanceof anchor.AnchorError)) {
+      throw new Error(`${label} should have failed with ${code}, got ${error ?? "success"}`);
+    }
+    if (error.error.errorCode.code !== code) {
+      throw new Error(`${label} should have failed with ${code}, got ${error.error.errorCode.code}`);
+    }
+    console.log(`Expected ${code} error`);
+  };
 
   before(async () => {
     console.log("\n=== Starting Test Environment Setup ===");
+    console.log("Funding test accounts...");
+    for (const account of [authority, investor]) {
+      const signature = await provider.connection.requestAirdrop(account.publicKey, 2 * LAMPORTS_PER_SOL);
+      await provider.connection.confirmTransaction(signature);
+    }
+
     console.log("Creating base token...");
     // Create base token
     baseTokenMint = await createMint(
@@ -56,6 +170,16 @@ describe("Solana Contract Test Suite", () => {
       investor.publicKey
     );
     console.log("User token account created:", userTokenAccount.toString());
+
+    await mintTo(
+      provider.connection,
+      authority,
+      baseTokenMint,
+      userTokenAccount,
+      authority,
+      10_000_000_000
+    );
+    console.log("Base tokens minted to investor");
     console.log("=== Test Environment Setup Completed ===\n");
   });
 
@@ -95,6 +219,10 @@ describe("Solana Contract Test Suite", () => {
       program.programId
     );
     console.log("Vault shares token account:", vaultShares.toString());
+    [withdrawEscrow] = await PublicKey.findProgramAddress(
+      [Buffer.from("withdraw_escrow"), vault.toBuffer()],
+      program.programId
+    );
 
     // Create user shares account
     userSharesAccount = await getAssociatedTokenAddress(
@@ -116,6 +244,7 @@ describe("Solana Contract Test Suite", () => {
           vaultBaseToken,
           vaultAuthority,
           vaultShares,
+          withdrawEscrow,
           tokenProgram: TOKEN_PROGRAM_ID,
           associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
           systemProgram: SystemProgram.programId,
@@ -135,17 +264,29 @@ describe("Solana Contract Test Suite", () => {
     const maxAllocation = 5000; // 50%
     const minExitAmount = 1000000; // 1 SOL
     
+    await expectError("set trading pair as a non-authority", "Unauthorized", () =>
+      program.methods
+        .setTradingPair(maxAllocation, minExitAmount)
+        .accounts({
+          authority: authority.publicKey,
+          strategy,
+          tokenMint: baseTokenMint,
+          vault,
+        })
+        .signers([authority])
+        .rpc()
+    );
+
     try {
       console.log("Calling set trading pair function...");
       const tx = await program.methods
         .setTradingPair(maxAllocation, minExitAmount)
         .accounts({
-          authority: authority.publicKey,
+          authority: provider.wallet.publicKey,
           strategy,
           tokenMint: baseTokenMint,
           vault,
         })
-        .signers([authority])
         .rpc();
       console.log("Trading pair set successfully, transaction signature:", tx);
       console.log("=== Trading Pair Set Test Completed ===\n");
@@ -155,28 +296,70 @@ describe("Solana Contract Test Suite", () => {
     }
   });
 
+  it("should successfully update investor", async () => {
+    console.log("\n=== Starting Update Investor Test ===");
+    try {
+      console.log("Calling update investor function...");
+      const tx = await program.methods
+        .updateInvestor(investor.publicKey)
+        .accounts({
+          authority: provider.wallet.publicKey,
+          vault,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+      console.log("Investor updated successfully, transaction signature:", tx);
+      console.log("=== Update Investor Test Completed ===\n");
+    } catch (error) {
+      console.error("Update investor failed:", error);
+      throw error;
+    }
+  });
+
   it("should successfully deposit", async () => {
     console.log("\n=== Starting Deposit Test ===");
     const amount = 1000000000; // 1 SOL
     
     try {
-      console.log("Calling deposit function...");
-      const tx = await program.methods
-        .deposit(amount)
+      console.log("Creating user shares account...");
+      await createAccount(
+        provider.connection,
+        investor,
+        vaultShares,
+        investor.publicKey
+      );
+      feeRecipientShares = (
+        await getOrCreateAssociatedTokenAccount(provider.connection, authority, vaultShares, provider.wallet.publicKey)
+      ).address;
+
+      console.log("Opening investor position...");
+      [investorPosition] = PublicKey.findProgramAddressSync(
+        [Buffer.from("position"), vault.toBuffer(), investor.publicKey.toBuffer()],
+        program.programId
+      );
+      await program.methods
+        .openPosition()
         .accounts({
           authority: investor.publicKey,
           vault,
-          vaultBaseToken,
-          vaultShares,
-          vaultAuthority,
-          userToken: userTokenAccount,
-          userShares: userSharesAccount,
-          tokenProgram: TOKEN_PROGRAM_ID,
+          position: investorPosition,
           systemProgram: SystemProgram.programId,
         })
         .signers([investor])
         .rpc();
+
+      console.log("Calling deposit function...");
+      const tx = await program.methods
+        .deposit(amount)
+        .accounts(investorAccounts())
+        .signers([investor])
+        .rpc();
       console.log("Deposit successful, transaction signature:", tx);
+
+      const position = await program.account.investorPosition.fetch(investorPosition);
+      if (position.deposited.toNumber() !== amount || position.costBasis.toNumber() !== amount || position.shares.isZero()) {
+        throw new Error("Deposit was not recorded in the investor position");
+      }
       console.log("=== Deposit Test Completed ===\n");
     } catch (error) {
       console.error("Deposit failed:", error);
@@ -192,32 +375,1092 @@ describe("Solana Contract Test Suite", () => {
       console.log("Calling withdraw function...");
       const tx = await program.methods
         .percentageWithdraw(percentage)
+        .accounts(investorAccounts())
+        .signers([investor])
+        .rpc();
+      console.log("Withdraw successful, transaction signature:", tx);
+      console.log("=== Withdraw Test Completed ===\n");
+    } catch (error) {
+      console.error("Withdraw failed:", error);
+      throw error;
+    }
+  });
+
+  it("should keep vault shares non-transferable", async () => {
+    console.log("\n=== Starting Share Transfer Test ===");
+    const shareAccount = await getAccount(provider.connection, userSharesAccount);
+    if (!shareAccount.isFrozen) {
+      throw new Error("Investor share account should be frozen");
+    }
+
+    console.log("Transferring shares outside the vault...");
+    let error: unknown;
+    try {
+      await transfer(provider.connection, investor, userSharesAccount, feeRecipientShares, investor, 1);
+    } catch (e) {
+      error = e;
+    }
+    const logs: string[] = (error as { logs?: string[] })?.logs ?? [];
+    if (!logs.some((log) => log.includes("Account is frozen"))) {
+      throw new Error(`Share transfer should have failed on a frozen account, got ${error ?? "success"}`);
+    }
+    console.log("=== Share Transfer Test Completed ===\n");
+  });
+
+  it("should successfully set up mock DEX pool", async () => {
+    console.log("\n=== Starting Mock DEX Setup Test ===");
+    try {
+      console.log("Creating trade token...");
+      tradeTokenMint = await createMint(
+        provider.connection,
+        authority,
+        authority.publicKey,
+        null,
+        9
+      );
+
+      [pool] = PublicKey.findProgramAddressSync(
+        [Buffer.from("pool"), baseTokenMint.toBuffer(), tradeTokenMint.toBuffer()],
+        mockDex.programId
+      );
+      [poolBaseVault] = PublicKey.findProgramAddressSync(
+        [Buffer.from("pool_vault"), pool.toBuffer(), baseTokenMint.toBuffer()],
+        mockDex.programId
+      );
+      [poolTradeVault] = PublicKey.findProgramAddressSync(
+        [Buffer.from("pool_vault"), pool.toBuffer(), tradeTokenMint.toBuffer()],
+        mockDex.programId
+      );
+
+      console.log("Initializing pool...");
+      await mockDex.methods
+        .initializePool(poolFeeBps)
+        .accounts({
+          payer: provider.wallet.publicKey,
+          pool,
+          mintA: baseTokenMint,
+          mintB: tradeTokenMint,
+          vaultA: poolBaseVault,
+          vaultB: poolTradeVault,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+          rent: anchor.web3.SYSVAR_RENT_PUBKEY,
+        })
+        .rpc();
+
+      console.log("Adding liquidity...");
+      const providerBase = await getOrCreateAssociatedTokenAccount(provider.connection, authority, baseTokenMint, authority.publicKey);
+      const providerTrade = await getOrCreateAssociatedTokenAccount(provider.connection, authority, tradeTokenMint, authority.publicKey);
+      await mintTo(provider.connection, authority, baseTokenMint, providerBase.address, authority, 100_000_000_000);
+      await mintTo(provider.connection, authority, tradeTokenMint, providerTrade.address, authority, 100_000_000_000);
+      await mockDex.methods
+        .addLiquidity(new anchor.BN(100_000_000_000), new anchor.BN(100_000_000_000))
+        .accounts({
+          provider: authority.publicKey,
+          pool,
+          vaultA: poolBaseVault,
+          vaultB: poolTradeVault,
+          providerTokenA: providerBase.address,
+          providerTokenB: providerTrade.address,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([authority])
+        .rpc();
+
+      console.log("Configuring vault strategy...");
+      vaultTradeToken = (
+        await getOrCreateAssociatedTokenAccount(provider.connection, authority, tradeTokenMint, vaultAuthority, true)
+      ).address;
+      await program.methods
+        .setTradingPair(5000, 0)
+        .accounts({
+          authority: provider.wallet.publicKey,
+          strategy,
+          tokenMint: tradeTokenMint,
+          vault,
+        })
+        .rpc();
+      await program.methods
+        .addSwapProgram(mockDex.programId, { constantProduct: {} })
+        .accounts({
+          authority: provider.wallet.publicKey,
+          strategy,
+          vault,
+        })
+        .rpc();
+      await program.methods
+        .updateStrategySettings(true, 900)
+        .accounts({
+          authority: provider.wallet.publicKey,
+          strategy,
+          vault,
+        })
+        .rpc();
+      console.log("=== Mock DEX Setup Test Completed ===\n");
+    } catch (error) {
+      console.error("Mock DEX setup failed:", error);
+      throw error;
+    }
+  });
+
+  const executeTradeAccounts = () => ({
+    oracle: provider.wallet.publicKey,
+    strategy,
+    vault,
+    vaultBaseToken,
+    vaultTokenAccount: vaultTradeToken,
+    vaultAuthority,
+    tokenMint: tradeTokenMint,
+    swapProgram: mockDex.programId,
+    instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+    priceSource: null,
+    basePriceSource: null,
+    tokenProgram: TOKEN_PROGRAM_ID,
+    systemProgram: SystemProgram.programId,
+  });
+
+  it("should execute buy signal through mock DEX", async () => {
+    console.log("\n=== Starting Buy Signal Test ===");
+    const amount = BigInt(100_000_000);
+    const expectedOut = await quote(amount, poolBaseVault, poolTradeVault);
+    const { data, remainingAccounts } = mockRoute(
+      amount, expectedOut, vaultBaseToken, vaultTradeToken, tradeTokenMint, poolBaseVault, poolTradeVault
+    );
+    const balanceBefore = (await getAccount(provider.connection, vaultTradeToken)).amount;
+
+    try {
+      console.log("Calling execute buy signal function...");
+      const tx = await program.methods
+        .executeBuySignal(
+          new anchor.BN(amount.toString()),
+          new anchor.BN(expectedOut.toString()),
+          new anchor.BN(5000),
+          new anchor.BN(Math.floor(Date.now() / 1000)),
+          new anchor.BN(signalNonce++),
+          data
+        )
+        .accounts(executeTradeAccounts())
+        .remainingAccounts(remainingAccounts)
+        .rpc();
+      console.log("Buy signal executed successfully, transaction signature:", tx);
+    } catch (error) {
+      console.error("Buy signal failed:", error);
+      throw error;
+    }
+
+    const balanceAfter = (await getAccount(provider.connection, vaultTradeToken)).amount;
+    if (balanceAfter - balanceBefore !== expectedOut) {
+      throw new Error(`Expected ${expectedOut} tokens, received ${balanceAfter - balanceBefore}`);
+    }
+    console.log("=== Buy Signal Test Completed ===\n");
+  });
+
+  it("should reject buy signal when swap output is below minimum", async () => {
+    console.log("\n=== Starting Buy Slippage Test ===");
+    const amount = BigInt(10_000_000);
+    const minOut = (await quote(amount, poolBaseVault, poolTradeVault)) + BigInt(1);
+    const { data, remainingAccounts } = mockRoute(
+      amount, minOut, vaultBaseToken, vaultTradeToken, tradeTokenMint, poolBaseVault, poolTradeVault
+    );
+
+    // The mock DEX enforces the quoted minimum out
+    await expectError("execute buy signal with unreachable minimum out", "SlippageToleranceExceeded", () =>
+      program.methods
+        .executeBuySignal(
+          new anchor.BN(amount.toString()),
+          new anchor.BN(minOut.toString()),
+          new anchor.BN(5000),
+          new anchor.BN(Math.floor(Date.now() / 1000)),
+          new anchor.BN(signalNonce++),
+          data
+        )
+        .accounts(executeTradeAccounts())
+        .remainingAccounts(remainingAccounts)
+        .rpc()
+    );
+    console.log("=== Buy Slippage Test Completed ===\n");
+  });
+
+  it("should execute sell signal through mock DEX", async () => {
+    console.log("\n=== Starting Sell Signal Test ===");
+    const amount = (await getAccount(provider.connection, vaultTradeToken)).amount / BigInt(2);
+    const expectedOut = await quote(amount, poolTradeVault, poolBaseVault);
+    const { data, remainingAccounts } = mockRoute(
+      amount, expectedOut, vaultTradeToken, vaultBaseToken, baseTokenMint, poolTradeVault, poolBaseVault
+    );
+    const balanceBefore = (await getAccount(provider.connection, vaultBaseToken)).amount;
+
+    try {
+      console.log("Calling execute sell signal function...");
+      const tx = await program.methods
+        .executeSellSignal(
+          new anchor.BN(amount.toString()),
+          new anchor.BN(expectedOut.toString()),
+          new anchor.BN(Math.floor(Date.now() / 1000)),
+          new anchor.BN(signalNonce++),
+          data
+        )
+        .accounts(executeTradeAccounts())
+        .remainingAccounts(remainingAccounts)
+        .rpc();
+      console.log("Sell signal executed successfully, transaction signature:", tx);
+    } catch (error) {
+      console.error("Sell signal failed:", error);
+      throw error;
+    }
+
+    const balanceAfter = (await getAccount(provider.connection, vaultBaseToken)).amount;
+    if (balanceAfter - balanceBefore !== expectedOut) {
+      throw new Error(`Expected ${expectedOut} base tokens, received ${balanceAfter - balanceBefore}`);
+    }
+    console.log("=== Sell Signal Test Completed ===\n");
+  });
+
+  it("should execute swap between vault tokens through mock DEX", async () => {
+    console.log("\n=== Starting Execute Swap Test ===");
+    const amount = BigInt(50_000_000);
+    const expectedOut = await quote(amount, poolBaseVault, poolTradeVault);
+    const { data, remainingAccounts } = mockRoute(
+      amount, expectedOut, vaultBaseToken, vaultTradeToken, tradeTokenMint, poolBaseVault, poolTradeVault
+    );
+    const balanceBefore = (await getAccount(provider.connection, vaultTradeToken)).amount;
+
+    try {
+      console.log("Calling execute swap function...");
+      const tx = await program.methods
+        .executeSwap(
+          new anchor.BN(amount.toString()),
+          new anchor.BN(expectedOut.toString()),
+          new anchor.BN(Math.floor(Date.now() / 1000)),
+          new anchor.BN(signalNonce++),
+          data
+        )
+        .accounts({
+          oracle: provider.wallet.publicKey,
+          strategy,
+          vault,
+          vaultInputToken: vaultBaseToken,
+          vaultOutputToken: vaultTradeToken,
+          vaultBaseToken,
+          vaultAuthority,
+          inputMint: baseTokenMint,
+          outputMint: tradeTokenMint,
+          swapProgram: mockDex.programId,
+          instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+          priceSource: null,
+          basePriceSource: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts(remainingAccounts)
+        .rpc();
+      console.log("Swap executed successfully, transaction signature:", tx);
+    } catch (error) {
+      console.error("Execute swap failed:", error);
+      throw error;
+    }
+
+    const balanceAfter = (await getAccount(provider.connection, vaultTradeToken)).amount;
+    if (balanceAfter - balanceBefore !== expectedOut) {
+      throw new Error(`Expected ${expectedOut} tokens, received ${balanceAfter - balanceBefore}`);
+    }
+    console.log("=== Execute Swap Test Completed ===\n");
+  });
+
+  it("should reject sell below minimum exit amount", async () => {
+    console.log("\n=== Starting Minimum Exit Test ===");
+    const balance = (await getAccount(provider.connection, vaultTradeToken)).amount;
+    const setMinExit = (minExit: bigint) =>
+      program.methods
+        .setTradingPair(5000, new anchor.BN(minExit.toString()))
+        .accounts({
+          authority: provider.wallet.publicKey,
+          strategy,
+          tokenMint: tradeTokenMint,
+          vault,
+        })
+        .rpc();
+    await setMinExit(balance / BigInt(4));
+
+    const amount = BigInt(1);
+    const { data, remainingAccounts } = mockRoute(
+      amount, BigInt(0), vaultTradeToken, vaultBaseToken, baseTokenMint, poolTradeVault, poolBaseVault
+    );
+
+    try {
+      await expectError("execute sell signal below minimum exit amount", "SellBelowMinExit", () =>
+        program.methods
+          .executeSellSignal(
+            new anchor.BN(amount.toString()),
+            new anchor.BN(0),
+            new anchor.BN(Math.floor(Date.now() / 1000)),
+            new anchor.BN(signalNonce++),
+            data
+          )
+          .accounts(executeTradeAccounts())
+          .remainingAccounts(remainingAccounts)
+          .rpc()
+      );
+    } finally {
+      await setMinExit(BigInt(0));
+    }
+    console.log("=== Minimum Exit Test Completed ===\n");
+  });
+
+  it("should reject replayed signal nonce", async () => {
+    console.log("\n=== Starting Signal Replay Test ===");
+    const amount = BigInt(1_000_000);
+    const { data, remainingAccounts } = mockRoute(
+      amount, BigInt(0), vaultBaseToken, vaultTradeToken, tradeTokenMint, poolBaseVault, poolTradeVault
+    );
+
+    await expectError("execute buy signal with a used nonce", "SignalReplayed", () =>
+      program.methods
+        .executeBuySignal(
+          new anchor.BN(amount.toString()),
+          new anchor.BN(0),
+          new anchor.BN(5000),
+          new anchor.BN(Math.floor(Date.now() / 1000)),
+          new anchor.BN(1),
+          data
+        )
+        .accounts(executeTradeAccounts())
+        .remainingAccounts(remainingAccounts)
+        .rpc()
+    );
+    console.log("=== Signal Replay Test Completed ===\n");
+  });
+
+  it("should successfully add and remove oracle", async () => {
+    console.log("\n=== Starting Oracle Registry Test ===");
+    const keeper = Keypair.generate();
+    
+    try {
+      console.log("Calling add oracle function...");
+      const addTx = await program.methods
+        .addOracle(keeper.publicKey)
+        .accounts({
+          authority: provider.wallet.publicKey,
+          strategy,
+          vault,
+        })
+        .rpc();
+      console.log("Oracle added successfully, transaction signature:", addTx);
+
+      console.log("Calling remove oracle function...");
+      const removeTx = await program.methods
+        .removeOracle(keeper.publicKey)
+        .accounts({
+          authority: provider.wallet.publicKey,
+          strategy,
+          vault,
+        })
+        .rpc();
+      console.log("Oracle removed successfully, transaction signature:", removeTx);
+      console.log("=== Oracle Registry Test Completed ===\n");
+    } catch (error) {
+      console.error("Oracle registry update failed:", error);
+      throw error;
+    }
+  });
+
+  it("should successfully set signal signer", async () => {
+    console.log("\n=== Starting Set Signal Signer Test ===");
+    const strategyEngine = Keypair.generate();
+    
+    try {
+      console.log("Calling set signal signer function...");
+      const tx = await program.methods
+        .setSignalSigner(strategyEngine.publicKey)
+        .accounts({
+          authority: provider.wallet.publicKey,
+          strategy,
+          vault,
+        })
+        .rpc();
+      console.log("Signal signer set successfully, transaction signature:", tx);
+
+      const strategyAccount = await program.account.strategy.fetch(strategy);
+      if (!strategyAccount.signalSigner?.equals(strategyEngine.publicKey)) {
+        throw new Error("Signal signer was not stored");
+      }
+      console.log("=== Set Signal Signer Test Completed ===\n");
+    } catch (error) {
+      console.error("Set signal signer failed:", error);
+      throw error;
+    }
+  });
+
+  it("should successfully allowlist swap program", async () => {
+    console.log("\n=== Starting Swap Program Allowlist Test ===");
+    const jupiterProgram = new PublicKey("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4");
+    
+    try {
+      console.log("Calling add swap program function...");
+      const tx = await program.methods
+        .addSwapProgram(jupiterProgram, { jupiter: {} })
+        .accounts({
+          authority: provider.wallet.publicKey,
+          strategy,
+          vault,
+        })
+        .rpc();
+      console.log("Swap program allowed successfully, transaction signature:", tx);
+
+      const strategyAccount = await program.account.strategy.fetch(strategy);
+      if (!strategyAccount.swapPrograms.some((p: any) => p.programId.equals(jupiterProgram) && p.venue.jupiter !== undefined)) {
+        throw new Error("Swap program was not allowlisted");
+      }
+      console.log("=== Swap Program Allowlist Test Completed ===\n");
+    } catch (error) {
+      console.error("Swap program allowlist update failed:", error);
+      throw error;
+    }
+  });
+
+  it("should successfully initialize and update price feed", async () => {
+    console.log("\n=== Starting Price Feed Test ===");
+    const [priceFeed] = PublicKey.findProgramAddressSync(
+      [Buffer.from("price_feed"), tradeTokenMint.toBuffer(), baseTokenMint.toBuffer()],
+      program.programId
+    );
+
+    try {
+      console.log("Calling initialize price feed function...");
+      await program.methods
+        .initializePriceFeed()
+        .accounts({
+          authority: provider.wallet.publicKey,
+          vault,
+          tokenMint: tradeTokenMint,
+          priceFeed,
+          systemProgram: SystemProgram.programId,
+        })
+        .rpc();
+
+      console.log("Calling update price function...");
+      await program.methods
+        .updatePrice(new anchor.BN("1000000000000000000"))
+        .accounts({
+          oracle: provider.wallet.publicKey,
+          strategy,
+          vault,
+          priceFeed,
+        })
+        .rpc();
+
+      console.log("Calling update prices batch function...");
+      await program.methods
+        .updatePricesBatch([new anchor.BN("2000000000000000000")])
+        .accounts({
+          oracle: provider.wallet.publicKey,
+          strategy,
+          vault,
+        })
+        .remainingAccounts([{ pubkey: priceFeed, isSigner: false, isWritable: true }])
+        .rpc();
+    } catch (error) {
+      console.error("Price feed update failed:", error);
+      throw error;
+    }
+
+    const feed = await program.account.priceFeed.fetch(priceFeed);
+    console.log("Price feed price:", feed.price.toString());
+    if (feed.price.toString() !== "2000000000000000000") {
+      throw new Error("Price feed was not updated by batch");
+    }
+    console.log("=== Price Feed Test Completed ===\n");
+  });
+
+  it("should successfully set pair price source", async () => {
+    console.log("\n=== Starting Price Source Test ===");
+    const setPriceSource = (priceSource: any, maxDeviationBps: number) =>
+      program.methods
+        .setPriceSource(priceSource, maxDeviationBps)
+        .accounts({
+          authority: provider.wallet.publicKey,
+          strategy,
+          tokenMint: tradeTokenMint,
+          vault,
+        })
+        .rpc();
+
+    try {
+      console.log("Setting Pyth price source...");
+      const feedId = Array.from(Buffer.alloc(32, 1));
+      await setPriceSource({ pyth: { feedId } }, 100);
+      let pair = (await program.account.strategy.fetch(strategy)).tradingPairs
+        .find((p) => p.tokenMint.equals(tradeTokenMint));
+      if (!pair.priceSource.pyth || pair.maxOracleDeviationBps !== 100) {
+        throw new Error("Pyth price source was not set");
+      }
+
+      console.log("Restoring price feed source...");
+      await setPriceSource({ priceFeed: {} }, 0);
+      pair = (await program.account.strategy.fetch(strategy)).tradingPairs
+        .find((p) => p.tokenMint.equals(tradeTokenMint));
+      if (!pair.priceSource.priceFeed) {
+        throw new Error("Price feed source was not restored");
+      }
+      console.log("=== Price Source Test Completed ===\n");
+    } catch (error) {
+      console.error("Set price source failed:", error);
+      throw error;
+    }
+  });
+
+  it("should withdraw at NAV including trading pair holdings", async () => {
+    console.log("\n=== Starting NAV Withdraw Test ===");
+    await expectError("partial withdraw without NAV accounts", "InvalidNavAccounts", () =>
+      program.methods
+        .partialWithdraw(new anchor.BN(1_000_000))
+        .accounts(investorAccounts())
+        .signers([investor])
+        .rpc()
+    );
+
+    try {
+      console.log("Calling partial withdraw with NAV accounts...");
+      const tx = await program.methods
+        .partialWithdraw(new anchor.BN(1_000_000))
+        .accounts(investorAccounts())
+        .remainingAccounts(navAccounts())
+        .signers([investor])
+        .rpc();
+      console.log("NAV withdraw successful, transaction signature:", tx);
+      console.log("=== NAV Withdraw Test Completed ===\n");
+    } catch (error) {
+      console.error("NAV withdraw failed:", error);
+      throw error;
+    }
+  });
+
+  it("should return ERC4626-style preview values", async () => {
+    console.log("\n=== Starting Preview Views Test ===");
+    const view = (method: any) => method.accounts(viewAccounts()).remainingAccounts(navAccounts()).view();
+
+    try {
+      const totalAssets = await view(program.methods.totalAssets());
+      const amount = new anchor.BN(1_000_000);
+      const depositShares = await view(program.methods.previewDeposit(amount));
+      const mintAssets = await view(program.methods.previewMint(depositShares));
+      const withdrawShares = await view(program.methods.previewWithdraw(amount));
+      const redeemAssets = await view(program.methods.previewRedeem(withdrawShares));
+      console.log("Total assets:", totalAssets.toString());
+      console.log("Preview deposit/mint:", depositShares.toString(), mintAssets.toString());
+      console.log("Preview withdraw/redeem:", withdrawShares.toString(), redeemAssets.toString());
+
+      // Rounding favors the vault in both directions
+      if (mintAssets.gt(amount) || withdrawShares.lt(depositShares) || redeemAssets.lt(amount)) {
+        throw new Error("Preview rounding does not favor the vault");
+      }
+
+      const maxWithdraw = await program.methods
+        .maxWithdraw()
+        .accounts({ ...viewAccounts(), position: investorPosition, ownerShares: userSharesAccount })
+        .remainingAccounts(navAccounts())
+        .view();
+      console.log("Max withdraw:", maxWithdraw.toString());
+      console.log("=== Preview Views Test Completed ===\n");
+    } catch (error) {
+      console.error("Preview views failed:", error);
+      throw error;
+    }
+  });
+
+  it("should mint and redeem exact share amounts", async () => {
+    console.log("\n=== Starting Mint And Redeem Test ===");
+    const shares = new anchor.BN(1_000_000);
+
+    try {
+      const sharesBefore = (await getAccount(provider.connection, userSharesAccount)).amount;
+      const maxAssetsIn = await program.methods
+        .previewMint(shares)
+        .accounts(viewAccounts())
+        .remainingAccounts(navAccounts())
+        .view();
+
+      console.log("Calling mint shares function...");
+      await program.methods
+        .mintShares(shares, maxAssetsIn)
+        .accounts(investorAccounts())
+        .remainingAccounts(navAccounts())
+        .signers([investor])
+        .rpc();
+
+      const sharesAfter = (await getAccount(provider.connection, userSharesAccount)).amount;
+      if (sharesAfter - sharesBefore !== BigInt(shares.toString())) {
+        throw new Error(`Expected ${shares} shares, minted ${sharesAfter - sharesBefore}`);
+      }
+
+      const minAssetsOut = await program.methods
+        .previewRedeem(shares)
+        .accounts(viewAccounts())
+        .remainingAccounts(navAccounts())
+        .view();
+
+      await expectError("redeem with unreachable minimum assets out", "MinAssetsOutNotMet", () =>
+        program.methods
+          .redeem(shares, minAssetsOut.addn(1))
+          .accounts(investorAccounts())
+          .remainingAccounts(navAccounts())
+          .signers([investor])
+          .rpc()
+      );
+
+      console.log("Calling redeem function...");
+      await program.methods
+        .redeem(shares, minAssetsOut)
+        .accounts(investorAccounts())
+        .remainingAccounts(navAccounts())
+        .signers([investor])
+        .rpc();
+
+      const sharesFinal = (await getAccount(provider.connection, userSharesAccount)).amount;
+      if (sharesFinal !== sharesBefore) {
+        throw new Error("Redeem did not burn the minted shares");
+      }
+      console.log("=== Mint And Redeem Test Completed ===\n");
+    } catch (error) {
+      console.error("Mint and redeem failed:", error);
+      throw error;
+    }
+  });
+
+  it("should redeem with liquidation and reject unneeded routes", async () => {
+    console.log("\n=== Starting Redeem With Liquidation Test ===");
+    const shares = new anchor.BN(1_000_000);
+
+    // The base balance covers a small redemption, so no position is sold
+    const amount = BigInt(1_000_000);
+    const { data, remainingAccounts } = mockRoute(
+      amount, BigInt(0), vaultTradeToken, vaultBaseToken, baseTokenMint, poolTradeVault, poolBaseVault
+    );
+    await expectError("redeem with liquidation with an unneeded route", "LiquidationRouteMismatch", () =>
+      program.methods
+        .redeemWithLiquidation(shares, new anchor.BN(0), [{
+          tokenMint: tradeTokenMint,
+          amountIn: new anchor.BN(amount.toString()),
+          minAmountOut: new anchor.BN(0),
+          accountsLen: remainingAccounts.length,
+          data,
+        }])
+        .accounts(investorAccounts())
+        .remainingAccounts([
+          ...navAccounts(true),
+          { pubkey: mockDex.programId, isSigner: false, isWritable: false },
+          ...remainingAccounts,
+        ])
+        .signers([investor])
+        .rpc()
+    );
+
+    try {
+      const minAssetsOut = await program.methods
+        .previewRedeem(shares)
+        .accounts(viewAccounts())
+        .remainingAccounts(navAccounts(true))
+        .view();
+
+      console.log("Calling redeem with liquidation function...");
+      const tx = await program.methods
+        .redeemWithLiquidation(shares, minAssetsOut, [])
+        .accounts(investorAccounts())
+        .remainingAccounts(navAccounts(true))
+        .signers([investor])
+        .rpc();
+      console.log("Redeem with liquidation successful, transaction signature:", tx);
+      console.log("=== Redeem With Liquidation Test Completed ===\n");
+    } catch (error) {
+      console.error("Redeem with liquidation failed:", error);
+      throw error;
+    }
+  });
+
+  it("should set and accrue vault fees", async () => {
+    console.log("\n=== Starting Vault Fees Test ===");
+    const feeAccounts = {
+      authority: provider.wallet.publicKey,
+      vault,
+      strategy,
+      vaultBaseToken,
+      vaultShares,
+      vaultAuthority,
+      feeRecipientShares,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+
+    await expectError("set fees above the maximum", "InvalidFee", () =>
+      program.methods
+        .setFees(5001, 0, provider.wallet.publicKey)
+        .accounts(feeAccounts)
+        .remainingAccounts(navAccounts())
+        .rpc()
+    );
+
+    try {
+      console.log("Calling set fees function...");
+      await program.methods
+        .setFees(200, 2000, provider.wallet.publicKey)
+        .accounts(feeAccounts)
+        .remainingAccounts(navAccounts())
+        .rpc();
+
+      const vaultAccount = await program.account.vault.fetch(vault);
+      if (vaultAccount.managementFeeBps !== 200 || vaultAccount.performanceFeeBps !== 2000) {
+        throw new Error("Fees were not updated");
+      }
+
+      // Anyone may accrue fees
+      console.log("Calling accrue fees function...");
+      const tx = await program.methods
+        .accrueFees()
+        .accounts({ ...feeAccounts, authority: investor.publicKey })
+        .remainingAccounts(navAccounts())
+        .signers([investor])
+        .rpc();
+      console.log("Fees accrued, transaction signature:", tx);
+
+      // Reset fees so later share math is not affected by time passing
+      await program.methods
+        .setFees(0, 0, provider.wallet.publicKey)
+        .accounts(feeAccounts)
+        .remainingAccounts(navAccounts())
+        .rpc();
+      console.log("=== Vault Fees Test Completed ===\n");
+    } catch (error) {
+      console.error("Vault fees failed:", error);
+      throw error;
+    }
+  });
+
+  it("should queue, cancel, process and claim withdrawals", async () => {
+    console.log("\n=== Starting Withdrawal Queue Test ===");
+    const [withdrawRequest] = PublicKey.findProgramAddressSync(
+      [Buffer.from("withdraw_request"), vault.toBuffer(), investor.publicKey.toBuffer()],
+      program.programId
+    );
+    const requestAccounts = {
+      authority: investor.publicKey,
+      vault,
+      withdrawRequest,
+      strategy,
+      vaultBaseToken,
+      withdrawEscrow,
+      vaultShares,
+      vaultAuthority,
+      userShares: userSharesAccount,
+      position: investorPosition,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      systemProgram: SystemProgram.programId,
+    };
+    const shares = new anchor.BN(1_000_000);
+
+    try {
+      const sharesBefore = (await getAccount(provider.connection, userSharesAccount)).amount;
+
+      console.log("Calling request withdraw function...");
+      await program.methods
+        .requestWithdraw(shares)
+        .accounts(requestAccounts)
+        .remainingAccounts(navAccounts())
+        .signers([investor])
+        .rpc();
+
+      console.log("Calling cancel withdrawal function...");
+      await program.methods
+        .cancelWithdrawal()
         .accounts({
           authority: investor.publicKey,
           vault,
+          withdrawRequest,
+          withdrawEscrow,
+          vaultShares,
+          vaultAuthority,
+          userShares: userSharesAccount,
+          position: investorPosition,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([investor])
+        .rpc();
+      if ((await getAccount(provider.connection, userSharesAccount)).amount !== sharesBefore) {
+        throw new Error("Cancel did not return the escrowed shares");
+      }
+
+      console.log("Requesting withdraw again...");
+      await program.methods
+        .requestWithdraw(shares)
+        .accounts(requestAccounts)
+        .remainingAccounts(navAccounts())
+        .signers([investor])
+        .rpc();
+
+      const vaultAccount = await program.account.vault.fetch(vault);
+      const [withdrawEpoch] = PublicKey.findProgramAddressSync(
+        [Buffer.from("withdraw_epoch"), vault.toBuffer(), vaultAccount.withdrawEpoch.toArrayLike(Buffer, "le", 8)],
+        program.programId
+      );
+
+      console.log("Calling process withdrawals function...");
+      await program.methods
+        .processWithdrawals()
+        .accounts({
+          keeper: provider.wallet.publicKey,
+          vault,
+          strategy,
           vaultBaseToken,
           vaultShares,
           vaultAuthority,
+          withdrawEscrow,
+          withdrawEpoch,
+          feeRecipientShares,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        })
+        .remainingAccounts(navAccounts())
+        .rpc();
+
+      const balanceBefore = (await getAccount(provider.connection, userTokenAccount)).amount;
+      console.log("Calling claim withdrawal function...");
+      const tx = await program.methods
+        .claimWithdrawal()
+        .accounts({
+          authority: investor.publicKey,
+          vault,
+          withdrawRequest,
+          withdrawEpoch,
+          vaultBaseToken,
+          vaultAuthority,
           userToken: userTokenAccount,
-          userShares: userSharesAccount,
+          position: investorPosition,
           tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([investor])
+        .rpc();
+      console.log("Withdrawal claimed, transaction signature:", tx);
+
+      const balanceAfter = (await getAccount(provider.connection, userTokenAccount)).amount;
+      if (balanceAfter <= balanceBefore) {
+        throw new Error("Claim did not pay the investor");
+      }
+      if ((await program.account.vault.fetch(vault)).reservedAssets.toNumber() !== 0) {
+        throw new Error("Claim did not release the reserved assets");
+      }
+      console.log("=== Withdrawal Queue Test Completed ===\n");
+    } catch (error) {
+      console.error("Withdrawal queue failed:", error);
+      throw error;
+    }
+  });
+
+  it("should gate deposits by investor access", async () => {
+    console.log("\n=== Starting Investor Access Test ===");
+    const outsider = Keypair.generate();
+    const [outsiderPosition] = PublicKey.findProgramAddressSync(
+      [Buffer.from("position"), vault.toBuffer(), outsider.publicKey.toBuffer()],
+      program.programId
+    );
+    const setAccess = (access: any) =>
+      program.methods
+        .setInvestorAccess(access)
+        .accounts({ authority: provider.wallet.publicKey, vault, systemProgram: SystemProgram.programId })
+        .rpc();
+
+    try {
+      console.log("Switching vault to allowlisted access...");
+      await setAccess({ allowlisted: {} });
+
+      console.log("Allowlisting investor...");
+      await program.methods
+        .addAllowedInvestor(investor.publicKey)
+        .accounts({
+          authority: provider.wallet.publicKey,
+          vault,
+          position: investorPosition,
           systemProgram: SystemProgram.programId,
         })
+        .rpc();
+      if (!(await program.account.investorPosition.fetch(investorPosition)).allowlisted) {
+        throw new Error("Investor was not allowlisted");
+      }
+
+      await expectError("add allowed investor as non-admin", "Unauthorized", () =>
+        program.methods
+          .addAllowedInvestor(outsider.publicKey)
+          .accounts({
+            authority: investor.publicKey,
+            vault,
+            position: outsiderPosition,
+            systemProgram: SystemProgram.programId,
+          })
+          .signers([investor])
+          .rpc()
+      );
+
+      console.log("Removing investor from allowlist...");
+      await program.methods
+        .removeAllowedInvestor(investor.publicKey)
+        .accounts({ authority: provider.wallet.publicKey, vault, position: investorPosition })
+        .rpc();
+      if ((await program.account.investorPosition.fetch(investorPosition)).allowlisted) {
+        throw new Error("Investor was not removed from the allowlist");
+      }
+
+      // Restore single investor access for later tests
+      await setAccess({ single: {} });
+      console.log("=== Investor Access Test Completed ===\n");
+    } catch (error) {
+      console.error("Investor access failed:", error);
+      throw error;
+    }
+  });
+
+  it("should enforce deposit caps and minimums", async () => {
+    console.log("\n=== Starting Deposit Limits Test ===");
+    const setLimits = (tvlCap: number, investorCap: number, minDeposit: number, minBalance: number) =>
+      program.methods
+        .setDepositLimits(new anchor.BN(tvlCap), new anchor.BN(investorCap), new anchor.BN(minDeposit), new anchor.BN(minBalance))
+        .accounts({ authority: provider.wallet.publicKey, vault, systemProgram: SystemProgram.programId })
+        .rpc();
+    const deposit = (amount: number) =>
+      program.methods
+        .deposit(new anchor.BN(amount))
+        .accounts(investorAccounts())
+        .remainingAccounts(navAccounts())
         .signers([investor])
         .rpc();
-      console.log("Withdraw successful, transaction signature:", tx);
-      console.log("=== Withdraw Test Completed ===\n");
+
+    try {
+      await setLimits(0, 0, 2_000_000, 0);
+      await expectError("deposit below minimum", "DepositBelowMinimum", () => deposit(1_000_000));
+
+      await setLimits(1, 0, 0, 0);
+      await expectError("deposit above TVL cap", "TvlCapExceeded", () => deposit(1_000_000));
+
+      await setLimits(0, 1, 0, 0);
+      await expectError("deposit above investor cap", "InvestorCapExceeded", () => deposit(1_000_000));
+
+      // A partial withdrawal may not leave dust below the minimum balance
+      const shares = (await getAccount(provider.connection, userSharesAccount)).amount;
+      await setLimits(0, 0, 0, Number.MAX_SAFE_INTEGER);
+      await expectError("withdraw below minimum balance", "BelowMinimumBalance", () =>
+        program.methods
+          .redeem(new anchor.BN((shares / BigInt(2)).toString()), new anchor.BN(0))
+          .accounts(investorAccounts())
+          .remainingAccounts(navAccounts())
+          .signers([investor])
+          .rpc()
+      );
+      const [withdrawRequest] = PublicKey.findProgramAddressSync(
+        [Buffer.from("withdraw_request"), vault.toBuffer(), investor.publicKey.toBuffer()],
+        program.programId
+      );
+      await expectError("request withdraw below minimum balance", "BelowMinimumBalance", () =>
+        program.methods
+          .requestWithdraw(new anchor.BN((shares / BigInt(2)).toString()))
+          .accounts({
+            authority: investor.publicKey,
+            vault,
+            withdrawRequest,
+            strategy,
+            vaultBaseToken,
+            withdrawEscrow,
+            vaultShares,
+            vaultAuthority,
+            userShares: userSharesAccount,
+            position: investorPosition,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId,
+          })
+          .remainingAccounts(navAccounts())
+          .signers([investor])
+          .rpc()
+      );
+
+      // Reset limits for later tests
+      await setLimits(0, 0, 0, 0);
+      console.log("=== Deposit Limits Test Completed ===\n");
     } catch (error) {
-      console.error("Withdraw failed:", error);
+      console.error("Deposit limits failed:", error);
       throw error;
     }
   });
 
+  it("should enforce deposit lock-up and early exit fee", async () => {
+    console.log("\n=== Starting Lock-Up Test ===");
+    const setLockup = (lockupPeriod: number, earlyExitFeeBps: number) =>
+      program.methods
+        .setLockup(new anchor.BN(lockupPeriod), earlyExitFeeBps)
+        .accounts({ authority: provider.wallet.publicKey, vault, systemProgram: SystemProgram.programId })
+        .rpc();
+    const partialWithdraw = (amount: number) =>
+      program.methods
+        .partialWithdraw(new anchor.BN(amount))
+        .accounts(investorAccounts())
+        .remainingAccounts(navAccounts())
+        .signers([investor])
+        .rpc();
+
+    try {
+      console.log("Setting a 30-day lock-up without early exits...");
+      await setLockup(30 * 24 * 60 * 60, 0);
+      await program.methods
+        .deposit(new anchor.BN(10_000_000))
+        .accounts(investorAccounts())
+        .remainingAccounts(navAccounts())
+        .signers([investor])
+        .rpc();
+
+      const position = await program.account.investorPosition.fetch(investorPosition);
+      if (position.unlockTime.toNumber() <= Math.floor(Date.now() / 1000)) {
+        throw new Error("Deposit did not start a lock-up");
+      }
+
+      await expectError("partial withdraw during the lock-up", "LockupActive", () => partialWithdraw(1_000_000));
+
+      console.log("Allowing early exits for a 1% fee...");
+      await setLockup(30 * 24 * 60 * 60, 100);
+      const sharesBefore = (await getAccount(provider.connection, userSharesAccount)).amount;
+      const previewShares = await program.methods
+        .previewWithdraw(new anchor.BN(1_000_000))
+        .accounts(viewAccounts())
+        .remainingAccounts(navAccounts())
+        .view();
+      await partialWithdraw(1_000_000);
+
+      // The fee is paid in extra shares burned
+      const burned = sharesBefore - (await getAccount(provider.connection, userSharesAccount)).amount;
+      if (burned <= BigInt(previewShares.toString())) {
+        throw new Error("Early exit fee was not charged");
+      }
+
+      await setLockup(0, 0);
+      console.log("=== Lock-Up Test Completed ===\n");
+    } catch (error) {
+      console.error("Lock-up failed:", error);
+      throw error;
+    }
+  });
+
+  it("should reject oracle registration by non-admin", async () => {
+    console.log("\n=== Starting Unauthorized Oracle Registration Test ===");
+    await expectError("add oracle as investor", "Unauthorized", () =>
+      program.methods
+        .addOracle(investor.publicKey)
+        .accounts({
+          authority: investor.publicKey,
+          strategy,
+          vault,
+        })
+        .signers([investor])
+        .rpc()
+    );
+    console.log("=== Unauthorized Oracle Registration Test Completed ===\n");
+  });
+
   it("should correctly handle unauthorized operation", async () => {
     console.log("\n=== Starting Unauthorized Operation Test ===");
-    try {
-      console.log("Attempting unauthorized operation...");
-      const tx = await program.methods
+    await expectError("update investor as investor", "Unauthorized", () =>
+      program.methods
         .updateInvestor(investor.publicKey)
         .accounts({
           authority: investor.publicKey,
@@ -225,10 +1468,8 @@ describe("Solana Contract Test Suite", () => {
           systemProgram: SystemProgram.programId,
         })
         .signers([investor])
-        .rpc();
-    } catch (error) {
-      console.log("Expected unauthorized error:", error);
-      console.log("=== Unauthorized Operation Test Completed ===\n");
-    }
+        .rpc()
+    );
+    console.log("=== Unauthorized Operation Test Completed ===\n");
   });
 });